@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS endpoints (
+    id TEXT PRIMARY KEY NOT NULL,
+    url TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS ping_results (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    endpoint_id TEXT NOT NULL REFERENCES endpoints (id) ON DELETE CASCADE,
+    status INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_ping_results_endpoint_timestamp
+    ON ping_results (endpoint_id, timestamp);
@@ -0,0 +1,2 @@
+-- Full endpoint settings (method, headers, timeouts, tags, ...) serialized as JSON.
+ALTER TABLE endpoints ADD COLUMN definition TEXT;
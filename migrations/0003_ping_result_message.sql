@@ -0,0 +1,2 @@
+-- Why a check failed, e.g. the assertion that did not hold.
+ALTER TABLE ping_results ADD COLUMN message TEXT;
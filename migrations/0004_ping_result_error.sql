@@ -0,0 +1,2 @@
+ALTER TABLE ping_results ADD COLUMN status_code INTEGER;
+ALTER TABLE ping_results ADD COLUMN error_kind TEXT;
@@ -0,0 +1 @@
+ALTER TABLE ping_results ADD COLUMN attempts INTEGER NOT NULL DEFAULT 1;
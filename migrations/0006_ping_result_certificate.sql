@@ -0,0 +1,2 @@
+ALTER TABLE ping_results ADD COLUMN cert_expires_at INTEGER;
+ALTER TABLE ping_results ADD COLUMN cert_issuer TEXT;
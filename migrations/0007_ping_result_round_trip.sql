@@ -0,0 +1 @@
+ALTER TABLE ping_results ADD COLUMN round_trip_ms INTEGER;
@@ -0,0 +1 @@
+ALTER TABLE ping_results ADD COLUMN metric REAL;
@@ -0,0 +1,5 @@
+ALTER TABLE ping_results ADD COLUMN dns_ms INTEGER;
+ALTER TABLE ping_results ADD COLUMN connect_ms INTEGER;
+ALTER TABLE ping_results ADD COLUMN tls_ms INTEGER;
+ALTER TABLE ping_results ADD COLUMN ttfb_ms INTEGER;
+ALTER TABLE ping_results ADD COLUMN download_ms INTEGER;
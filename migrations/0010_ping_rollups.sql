@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS ping_rollups (
+    endpoint_id TEXT NOT NULL REFERENCES endpoints (id) ON DELETE CASCADE,
+    resolution_ms INTEGER NOT NULL,
+    bucket_start INTEGER NOT NULL,
+    total INTEGER NOT NULL,
+    up INTEGER NOT NULL,
+    degraded INTEGER NOT NULL,
+    PRIMARY KEY (endpoint_id, resolution_ms, bucket_start)
+);
+
+INSERT OR IGNORE INTO ping_rollups
+SELECT endpoint_id, 60000, timestamp / 60000 * 60000, COUNT(*), SUM(status = 1), SUM(status = 2)
+FROM ping_results
+GROUP BY endpoint_id, timestamp / 60000;
+
+INSERT OR IGNORE INTO ping_rollups
+SELECT endpoint_id, 3600000, timestamp / 3600000 * 3600000, COUNT(*), SUM(status = 1), SUM(status = 2)
+FROM ping_results
+GROUP BY endpoint_id, timestamp / 3600000;
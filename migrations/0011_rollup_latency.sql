@@ -0,0 +1,9 @@
+-- Summed latency of the checks in each rollup, for the average over a range.
+ALTER TABLE ping_rollups ADD COLUMN latency_sum INTEGER NOT NULL DEFAULT 0;
+
+UPDATE ping_rollups SET latency_sum = COALESCE((
+    SELECT SUM(latency_ms) FROM ping_results
+    WHERE ping_results.endpoint_id = ping_rollups.endpoint_id
+      AND ping_results.timestamp >= ping_rollups.bucket_start
+      AND ping_results.timestamp < ping_rollups.bucket_start + ping_rollups.resolution_ms
+), 0);
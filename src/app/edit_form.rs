@@ -0,0 +1,119 @@
+use anyhow::{anyhow, bail, Result};
+use tui_textarea::{CursorMove, TextArea};
+
+use crate::config::validate_url;
+use crate::ping::{Endpoint, EndpointKind};
+
+/// Labels of the edit form fields, in display order.
+pub const EDIT_FIELDS: [&str; 6] = [
+    "URL",
+    "Name",
+    "Interval (s)",
+    "Timeout (s)",
+    "Max retries",
+    "Latency warning (ms)",
+];
+
+/// Form for the settings of an existing endpoint. Settings the form doesn't
+/// show are kept as they are.
+pub struct EditForm {
+    pub endpoint: Endpoint,
+    pub inputs: Vec<TextArea<'static>>,
+    pub focused: usize,
+    pub error: Option<String>,
+}
+
+impl EditForm {
+    pub fn new(endpoint: Endpoint) -> Self {
+        let values = [
+            endpoint.url.clone(),
+            endpoint.name.clone().unwrap_or_default(),
+            endpoint.interval_seconds.to_string(),
+            endpoint.timeout_seconds.to_string(),
+            endpoint.retry.max_retries.to_string(),
+            endpoint
+                .latency_warn_ms
+                .map_or(String::new(), |ms| ms.to_string()),
+        ];
+
+        Self {
+            endpoint,
+            inputs: values
+                .into_iter()
+                .map(|value| {
+                    let mut input = TextArea::new(vec![value]);
+                    input.move_cursor(CursorMove::End);
+                    input
+                })
+                .collect(),
+            focused: 0,
+            error: None,
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        self.focused = (self.focused + 1) % self.inputs.len();
+    }
+
+    pub fn previous_field(&mut self) {
+        self.focused = self.focused.checked_sub(1).unwrap_or(self.inputs.len() - 1);
+    }
+
+    pub fn focused_input(&mut self) -> &mut TextArea<'static> {
+        &mut self.inputs[self.focused]
+    }
+
+    /// The endpoint with the values of the form applied.
+    pub fn apply(&self) -> Result<Endpoint> {
+        let values: Vec<String> = self
+            .inputs
+            .iter()
+            .map(|input| input.lines().join("").trim().to_string())
+            .collect();
+
+        let url = values[0].clone();
+        validate_url(&url).map_err(|e| anyhow!("URL: {}", e))?;
+
+        let interval_seconds = parse_positive(EDIT_FIELDS[2], &values[2])?;
+        let timeout_seconds = parse_positive(EDIT_FIELDS[3], &values[3])?;
+        let max_retries = values[4]
+            .parse()
+            .map_err(|_| anyhow!("{} must be a whole number", EDIT_FIELDS[4]))?;
+        let latency_warn_ms = match values[5].as_str() {
+            "" => None,
+            value => Some(parse_positive(EDIT_FIELDS[5], value)?),
+        };
+
+        // options of the old kind would stay behind and make the endpoint invalid
+        let kind = Endpoint::new(url.clone()).kind();
+        if kind != self.endpoint.kind() {
+            bail!(
+                "URL: a {} endpoint can't be turned into a {} one, add it as a new endpoint",
+                self.endpoint.kind().as_str(),
+                kind.as_str()
+            );
+        }
+        if kind == EndpointKind::Heartbeat && latency_warn_ms.is_some() {
+            bail!(
+                "{} is not supported for heartbeat endpoints",
+                EDIT_FIELDS[5]
+            );
+        }
+
+        let mut endpoint = self.endpoint.clone();
+        endpoint.url = url;
+        endpoint.name = Some(values[1].clone()).filter(|name| !name.is_empty());
+        endpoint.interval_seconds = interval_seconds;
+        endpoint.timeout_seconds = timeout_seconds;
+        endpoint.retry.max_retries = max_retries;
+        endpoint.latency_warn_ms = latency_warn_ms;
+        Ok(endpoint)
+    }
+}
+
+fn parse_positive(field: &str, value: &str) -> Result<u64> {
+    match value.parse() {
+        Ok(0) | Err(_) => bail!("{} must be a number greater than 0", field),
+        Ok(value) => Ok(value),
+    }
+}
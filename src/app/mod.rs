@@ -1,13 +1,16 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use ratatui::widgets::TableState;
-use std::collections::HashMap;
 use tui_textarea::TextArea;
-use uuid::Uuid;
 
+use crate::ping::{Endpoint, PingResult, Status};
 use crate::storage::EndpointStats;
 use crate::visitor::StorageVisitor;
 
+pub use edit_form::{EditForm, EDIT_FIELDS};
+
+mod edit_form;
+
 #[derive(Debug, Clone)]
 pub struct LogEntry {
     pub timestamp: DateTime<Utc>,
@@ -27,31 +30,55 @@ pub enum LogLevel {
 pub enum InputMode {
     Normal,
     Adding,
+    Editing,
+    ConfirmingRemoval,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TimeRange {
     Minutes(i64),
+    Hours(i64),
+    Days(i64),
 }
 
 impl TimeRange {
     pub fn display_name(&self) -> String {
         match self {
             TimeRange::Minutes(n) => format!("{}m", n),
+            TimeRange::Hours(n) => format!("{}h", n),
+            TimeRange::Days(n) => format!("{}d", n),
         }
     }
 
-    pub fn get_duration_hours(&self) -> Option<i64> {
+    pub fn minutes(&self) -> i64 {
         match self {
-            TimeRange::Minutes(n) => Some(n / 60),
+            TimeRange::Minutes(n) => *n,
+            TimeRange::Hours(n) => n * 60,
+            TimeRange::Days(n) => n * 24 * 60,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::minutes(self.minutes())
+    }
+
+    /// Length of one uptime block and chart point, picked so that every
+    /// range comes out at a few dozen of them.
+    pub fn bucket(&self) -> Duration {
+        match self.minutes() {
+            ..=60 => Duration::minutes(1),
+            61..=360 => Duration::minutes(5),
+            361..=1440 => Duration::minutes(30),
+            1441..=10080 => Duration::hours(3),
+            _ => Duration::hours(12),
         }
     }
 }
 
+/// One bucket of the uptime blocks, `None` when no check ran in it.
 #[derive(Debug, Clone)]
 pub struct UptimeBlock {
-    pub timestamp: DateTime<Utc>,
-    pub status: bool,
+    pub status: Option<Status>,
 }
 
 pub struct App {
@@ -60,9 +87,15 @@ pub struct App {
     pub table_state: TableState,
     pub input_mode: InputMode,
     pub url_input: TextArea<'static>,
-    pub uptime_history: HashMap<Uuid, Vec<(f64, f64)>>,
-    pub uptime_blocks: HashMap<Uuid, Vec<UptimeBlock>>,
+    pub edit_form: Option<EditForm>,
+    /// Uptime chart points and blocks of the selected endpoint.
+    pub uptime_history: Vec<(f64, f64)>,
+    pub uptime_blocks: Vec<UptimeBlock>,
+    /// Checks of the selected endpoint within the current time range.
+    pub selected_results: Vec<PingResult>,
     pub developer_mode: bool,
+    pub show_details: bool,
+    pub show_latency_chart: bool,
     pub logs: Vec<LogEntry>,
     pub log_scroll: usize,
     pub time_ranges: Vec<TimeRange>,
@@ -74,7 +107,14 @@ impl App {
         let mut table_state = TableState::default();
         table_state.select(Some(0));
 
-        let time_ranges = vec![TimeRange::Minutes(60)];
+        let time_ranges = vec![
+            TimeRange::Minutes(15),
+            TimeRange::Hours(1),
+            TimeRange::Hours(6),
+            TimeRange::Hours(24),
+            TimeRange::Days(7),
+            TimeRange::Days(30),
+        ];
 
         Self {
             endpoints_stats: Vec::new(),
@@ -82,13 +122,17 @@ impl App {
             table_state,
             input_mode: InputMode::Normal,
             url_input: TextArea::default(),
-            uptime_history: HashMap::new(),
-            uptime_blocks: HashMap::new(),
+            edit_form: None,
+            uptime_history: Vec::new(),
+            uptime_blocks: Vec::new(),
+            selected_results: Vec::new(),
             developer_mode: false,
+            show_details: false,
+            show_latency_chart: false,
             logs: Vec::new(),
             log_scroll: 0,
             time_ranges,
-            selected_time_range: 0,
+            selected_time_range: 1,
         }
     }
 
@@ -109,6 +153,10 @@ impl App {
         self.log_scroll = 0;
     }
 
+    pub fn toggle_latency_chart(&mut self) {
+        self.show_latency_chart = !self.show_latency_chart;
+    }
+
     pub fn scroll_logs_up(&mut self) {
         if self.log_scroll > 0 {
             self.log_scroll -= 1;
@@ -125,107 +173,73 @@ impl App {
         &self.time_ranges[self.selected_time_range]
     }
 
-    pub fn update_stats(&mut self, storage: &StorageVisitor) -> Result<()> {
-        self.endpoints_stats = storage.get_endpoint_stats()?;
-
-        let current_time_range = self.get_current_time_range().clone();
-
-        let endpoint_ids: Vec<Uuid> = self.endpoints_stats.iter().map(|s| s.endpoint.id).collect();
-
-        for endpoint_id in endpoint_ids {
-            let hours = current_time_range.get_duration_hours().unwrap_or(24);
-            let history = storage.get_uptime_history(endpoint_id, hours)?;
-            let chart_data: Vec<(f64, f64)> = if !history.is_empty() {
-                let now = Utc::now();
-                history
-                    .into_iter()
-                    .map(|(timestamp, uptime)| {
-                        let hours_ago = (now - timestamp).num_minutes() as f64 / 60.0;
-                        let x_pos = hours as f64 - hours_ago.max(0.0).min(hours as f64);
-                        (x_pos, uptime)
-                    })
-                    .collect()
-            } else {
-                vec![(0.0, 0.0), (hours as f64, 0.0)]
-            };
-
-            self.uptime_history.insert(endpoint_id, chart_data);
+    pub fn selected_endpoint(&self) -> Option<&Endpoint> {
+        self.endpoints_stats
+            .get(self.selected_endpoint)
+            .map(|stats| &stats.endpoint)
+    }
 
-            if !self.uptime_blocks.contains_key(&endpoint_id) {
-                self.update_uptime_blocks(storage, endpoint_id, &current_time_range)?;
-            }
-        }
+    pub fn next_time_range(&mut self) {
+        self.selected_time_range = (self.selected_time_range + 1) % self.time_ranges.len();
+    }
 
-        Ok(())
+    pub fn previous_time_range(&mut self) {
+        self.selected_time_range = self
+            .selected_time_range
+            .checked_sub(1)
+            .unwrap_or(self.time_ranges.len() - 1);
     }
 
-    pub fn add_realtime_block(&mut self, endpoint_id: Uuid, status: bool) {
+    pub async fn update_stats(&mut self, storage: &StorageVisitor) -> Result<()> {
+        let time_range = *self.get_current_time_range();
         let now = Utc::now();
-        let new_block = UptimeBlock {
-            timestamp: now,
-            status,
-        };
-
-        // Get or create the blocks vector for this endpoint
-        let blocks = self
-            .uptime_blocks
-            .entry(endpoint_id)
-            .or_insert_with(Vec::new);
+        let since = now - time_range.duration();
 
-        // Add the new block
-        blocks.push(new_block);
-
-        // Keep only the last 60 blocks (for 60-minute window)
-        if blocks.len() > 60 {
-            blocks.drain(0..blocks.len() - 60);
+        self.endpoints_stats = storage.get_endpoint_stats(since).await?;
+        // the selected endpoint may have been removed
+        if self.selected_endpoint >= self.endpoints_stats.len() {
+            self.selected_endpoint = self.endpoints_stats.len().saturating_sub(1);
+            self.table_state.select(Some(self.selected_endpoint));
         }
 
-        // Sort by timestamp to ensure proper order
-        blocks.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-    }
-
-    fn update_uptime_blocks(
-        &mut self,
-        storage: &StorageVisitor,
-        endpoint_id: Uuid,
-        time_range: &TimeRange,
-    ) -> Result<()> {
-        let blocks = match time_range {
-            TimeRange::Minutes(minutes) => {
-                self.generate_minute_blocks(storage, endpoint_id, *minutes)?
-            }
+        let Some(endpoint_id) = self
+            .endpoints_stats
+            .get(self.selected_endpoint)
+            .map(|stats| stats.endpoint.id)
+        else {
+            self.selected_results.clear();
+            self.uptime_history.clear();
+            self.uptime_blocks.clear();
+            return Ok(());
         };
 
-        self.uptime_blocks.insert(endpoint_id, blocks);
-        Ok(())
-    }
+        self.selected_results = storage.get_results(endpoint_id, since).await?;
+
+        let history = storage
+            .get_uptime_history(endpoint_id, since, time_range.bucket())
+            .await?;
+
+        // x is minutes into the range, buckets without checks are left out
+        self.uptime_history = history
+            .iter()
+            .filter(|point| point.checks > 0)
+            .map(|point| {
+                let minutes_ago = (now - point.timestamp).num_seconds() as f64 / 60.0;
+                let range = time_range.minutes() as f64;
+                (
+                    (range - minutes_ago).clamp(0.0, range),
+                    point.uptime_percentage,
+                )
+            })
+            .collect();
+        self.uptime_blocks = history
+            .iter()
+            .map(|point| UptimeBlock {
+                status: point.status(),
+            })
+            .collect();
 
-    fn generate_minute_blocks(
-        &self,
-        storage: &StorageVisitor,
-        endpoint_id: Uuid,
-        minutes: i64,
-    ) -> Result<Vec<UptimeBlock>> {
-        let history = storage.get_uptime_history(endpoint_id, minutes / 60 + 1)?;
-        let mut blocks = Vec::new();
-
-        let now = Utc::now();
-        let start_time = now - chrono::Duration::minutes(minutes);
-
-        for (timestamp, uptime) in &history {
-            if *timestamp >= start_time {
-                let minutes_ago = (now - *timestamp).num_minutes();
-                if minutes_ago >= 0 && minutes_ago < minutes {
-                    blocks.push(UptimeBlock {
-                        timestamp: *timestamp,
-                        status: *uptime > 0.0,
-                    });
-                }
-            }
-        }
-
-        blocks.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
-        Ok(blocks)
+        Ok(())
     }
 
     pub fn next_endpoint(&mut self) {
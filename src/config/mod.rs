@@ -1,6 +1,879 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::Duration;
+use regex::Regex;
+use reqwest::{
+    header::{HeaderName, HeaderValue},
+    Url,
+};
 use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    ffi::OsString,
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::ping::{
+    parse_nameserver, Assertion, ConcurrencyLimits, Endpoint, EndpointKind, ErrorKind, RecordType,
+    RetryPolicy,
+};
+use crate::storage::RetentionPolicy;
+
+pub use writer::ConfigWriter;
+
+mod writer;
+
+/// Environment variable naming the config file, used when `--config` isn't given.
+pub const CONFIG_ENV: &str = "PINGME_CONFIG";
+
+pub const DEFAULT_INTERVAL_SECONDS: u64 = 60;
+pub const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+pub const DEFAULT_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 500;
+pub const DEFAULT_RETRY_MAX_DELAY_MS: u64 = 5000;
+pub const DEFAULT_MAX_CONCURRENT_CHECKS: usize = 16;
+pub const DEFAULT_MAX_CHECKS_PER_HOST: usize = 2;
+pub const DEFAULT_CERT_WARNING_DAYS: u64 = 14;
+pub const DEFAULT_HEARTBEAT_GRACE_SECONDS: u64 = 60;
+pub const DEFAULT_RAW_RETENTION_DAYS: u64 = 7;
+pub const DEFAULT_MINUTE_ROLLUP_RETENTION_DAYS: u64 = 30;
+pub const DEFAULT_HOURLY_ROLLUP_RETENTION_DAYS: u64 = 365;
+
+const METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    #[serde(default)]
     pub endpoints: Vec<String>,
+    #[serde(default, rename = "endpoint")]
+    pub endpoint_tables: Vec<EndpointConfig>,
+    pub interval_seconds: Option<u64>,
+    pub max_retries: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub retry_on: Option<Vec<ErrorKind>>,
+    pub timeout_seconds: Option<u64>,
+    pub max_concurrent_checks: Option<usize>,
+    pub max_checks_per_host: Option<usize>,
+    pub cert_warning_days: Option<u64>,
+    pub latency_warn_ms: Option<u64>,
+    pub heartbeat_listen: Option<String>,
+    pub raw_retention_days: Option<u64>,
+    pub minute_rollup_retention_days: Option<u64>,
+    pub hourly_rollup_retention_days: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointConfig {
+    pub url: String,
+    pub name: Option<String>,
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<String>,
+    #[serde(default)]
+    pub expected_status: Vec<u16>,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub soft_assertions: Vec<Assertion>,
+    pub send: Option<String>,
+    pub expect: Option<String>,
+    pub record_type: Option<RecordType>,
+    pub nameserver: Option<String>,
+    #[serde(default)]
+    pub expected_records: Vec<String>,
+    pub grpc_service: Option<String>,
+    #[serde(default)]
+    pub command: Vec<String>,
+    pub latency_pattern: Option<String>,
+    pub metric_pattern: Option<String>,
+    pub token: Option<String>,
+    pub grace_seconds: Option<u64>,
+    pub timeout_seconds: Option<u64>,
+    pub interval_seconds: Option<u64>,
+    pub max_retries: Option<u32>,
+    pub retry_base_delay_ms: Option<u64>,
+    pub retry_max_delay_ms: Option<u64>,
+    pub retry_on: Option<Vec<ErrorKind>>,
+    pub cert_warning_days: Option<u64>,
+    pub latency_warn_ms: Option<u64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Global settings applied to every endpoint that does not override them.
+#[derive(Debug, Clone)]
+pub struct Defaults {
+    pub interval_seconds: u64,
+    pub timeout_seconds: u64,
+    pub retry: RetryPolicy,
+    pub cert_warning_days: u64,
+    pub latency_warn_ms: Option<u64>,
+}
+
+impl Default for Defaults {
+    fn default() -> Self {
+        Self {
+            interval_seconds: DEFAULT_INTERVAL_SECONDS,
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            retry: RetryPolicy::default(),
+            cert_warning_days: DEFAULT_CERT_WARNING_DAYS,
+            latency_warn_ms: None,
+        }
+    }
+}
+
+impl Defaults {
+    pub fn endpoint(&self, url: String) -> Endpoint {
+        Endpoint {
+            interval_seconds: self.interval_seconds,
+            timeout_seconds: self.timeout_seconds,
+            retry: self.retry.clone(),
+            cert_warning_days: self.cert_warning_days,
+            latency_warn_ms: self.latency_warn_ms,
+            ..Endpoint::new(url)
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn defaults(&self) -> Result<Defaults> {
+        let defaults = Defaults::default();
+        let defaults = Defaults {
+            interval_seconds: self.interval_seconds.unwrap_or(defaults.interval_seconds),
+            timeout_seconds: self.timeout_seconds.unwrap_or(defaults.timeout_seconds),
+            retry: resolve_retry(
+                &defaults.retry,
+                self.max_retries,
+                self.retry_base_delay_ms,
+                self.retry_max_delay_ms,
+                &self.retry_on,
+            )?,
+            cert_warning_days: self.cert_warning_days.unwrap_or(defaults.cert_warning_days),
+            latency_warn_ms: self.latency_warn_ms,
+        };
+
+        if defaults.interval_seconds == 0 {
+            bail!("interval_seconds must be greater than 0");
+        }
+        if defaults.latency_warn_ms == Some(0) {
+            bail!("latency_warn_ms must be greater than 0");
+        }
+        if defaults.timeout_seconds == 0 {
+            bail!("timeout_seconds must be greater than 0");
+        }
+
+        Ok(defaults)
+    }
+
+    pub fn concurrency_limits(&self) -> Result<ConcurrencyLimits> {
+        let limits = ConcurrencyLimits::default();
+        let limits = ConcurrencyLimits {
+            max_checks: self.max_concurrent_checks.unwrap_or(limits.max_checks),
+            max_per_host: self.max_checks_per_host.unwrap_or(limits.max_per_host),
+        };
+
+        if limits.max_checks == 0 {
+            bail!("max_concurrent_checks must be greater than 0");
+        }
+        if limits.max_per_host == 0 {
+            bail!("max_checks_per_host must be greater than 0");
+        }
+
+        Ok(limits)
+    }
+
+    pub fn retention(&self) -> Result<RetentionPolicy> {
+        let days = [
+            (
+                "raw_retention_days",
+                self.raw_retention_days
+                    .unwrap_or(DEFAULT_RAW_RETENTION_DAYS),
+            ),
+            (
+                "minute_rollup_retention_days",
+                self.minute_rollup_retention_days
+                    .unwrap_or(DEFAULT_MINUTE_ROLLUP_RETENTION_DAYS),
+            ),
+            (
+                "hourly_rollup_retention_days",
+                self.hourly_rollup_retention_days
+                    .unwrap_or(DEFAULT_HOURLY_ROLLUP_RETENTION_DAYS),
+            ),
+        ];
+
+        for (option, value) in days {
+            if value == 0 {
+                bail!("{} must be greater than 0", option);
+            }
+        }
+        // a coarser tier is what is left once the finer one is dropped
+        for pair in days.windows(2) {
+            if pair[1].1 < pair[0].1 {
+                bail!("{} must not be shorter than {}", pair[1].0, pair[0].0);
+            }
+        }
+
+        let [raw, minute_rollups, hourly_rollups] =
+            days.map(|(_, value)| Duration::days(value as i64));
+        Ok(RetentionPolicy {
+            raw,
+            minute_rollups,
+            hourly_rollups,
+        })
+    }
+
+    pub fn heartbeat_listen(&self) -> Result<Option<SocketAddr>> {
+        self.heartbeat_listen
+            .as_deref()
+            .map(|address| {
+                address.parse().map_err(|_| {
+                    anyhow!(
+                        "heartbeat_listen {:?} is not a valid address, expected ip:port",
+                        address
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Validates every entry of both the plain `endpoints` list and the
+    /// `[[endpoint]]` tables and resolves them against the global defaults.
+    pub fn resolve_endpoints(&self) -> Result<Vec<Endpoint>> {
+        let defaults = self.defaults()?;
+        let mut endpoints = Vec::new();
+        let mut seen: HashMap<String, String> = HashMap::new();
+        let mut tokens: HashMap<String, String> = HashMap::new();
+
+        for (i, url) in self.endpoints.iter().enumerate() {
+            let location = format!("endpoints[{}]", i);
+            validate_url(url).map_err(|e| anyhow!("{} ({:?}): {}", location, url, e))?;
+            check_duplicate(&mut seen, "url", url, &location)?;
+            let endpoint = defaults.endpoint(url.clone());
+            self.check_heartbeat_listen(&endpoint)
+                .map_err(|e| anyhow!("{} ({:?}): {}", location, url, e))?;
+            endpoints.push(endpoint);
+        }
+
+        for (i, table) in self.endpoint_tables.iter().enumerate() {
+            let location = format!("[[endpoint]] #{}", i + 1);
+            let endpoint = table
+                .resolve(&defaults)
+                .map_err(|e| anyhow!("{} ({:?}): {}", location, table.url, e))?;
+            check_duplicate(&mut seen, "url", &endpoint.url, &location)?;
+            self.check_heartbeat_listen(&endpoint)
+                .map_err(|e| anyhow!("{} ({:?}): {}", location, table.url, e))?;
+            if let Some(token) = &endpoint.token {
+                check_duplicate(&mut tokens, "token", token, &location)?;
+            }
+            endpoints.push(endpoint);
+        }
+
+        Ok(endpoints)
+    }
+
+    // without the server nothing would ever report to a heartbeat endpoint
+    fn check_heartbeat_listen(&self, endpoint: &Endpoint) -> Result<()> {
+        if endpoint.kind() == EndpointKind::Heartbeat && self.heartbeat_listen.is_none() {
+            bail!("heartbeat endpoints need heartbeat_listen, e.g. heartbeat_listen = \"127.0.0.1:9876\"");
+        }
+        Ok(())
+    }
+}
+
+/// Finds the config file to load: `--config`, then `PINGME_CONFIG`, then the
+/// default locations. A file that was asked for has to exist. A url given on
+/// the command line wins over everything but `--config`.
+pub fn locate(config_arg: Option<&str>, url_given: bool) -> Result<Option<PathBuf>> {
+    let env_config = env::var_os(CONFIG_ENV)
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from);
+    let config_home = config_home(env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME"));
+
+    find_config(
+        config_arg.map(PathBuf::from),
+        url_given,
+        env_config,
+        &default_locations(Path::new(""), config_home.as_deref()),
+    )
+}
+
+fn find_config(
+    config_arg: Option<PathBuf>,
+    url_given: bool,
+    env_config: Option<PathBuf>,
+    locations: &[PathBuf],
+) -> Result<Option<PathBuf>> {
+    let requested = match config_arg {
+        Some(path) => Some((path, "--config")),
+        None if url_given => return Ok(None),
+        None => env_config.map(|path| (path, CONFIG_ENV)),
+    };
+
+    if let Some((path, source)) = requested {
+        if !path.is_file() {
+            bail!(
+                "Config file {} given by {} not found",
+                path.display(),
+                source
+            );
+        }
+        return Ok(Some(path));
+    }
+
+    Ok(locations.iter().find(|path| path.is_file()).cloned())
+}
+
+/// `$XDG_CONFIG_HOME` when it is absolute, otherwise `~/.config`.
+fn config_home(xdg_config_home: Option<OsString>, home: Option<OsString>) -> Option<PathBuf> {
+    xdg_config_home
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            home.filter(|home| !home.is_empty())
+                .map(|home| PathBuf::from(home).join(".config"))
+        })
+}
+
+/// `.ping` and `pingme.toml` in `current_dir`, then `pingme/config.toml` in
+/// the user config directory.
+fn default_locations(current_dir: &Path, config_home: Option<&Path>) -> Vec<PathBuf> {
+    let mut locations = vec![current_dir.join(".ping"), current_dir.join("pingme.toml")];
+    if let Some(dir) = config_home {
+        locations.push(dir.join("pingme").join("config.toml"));
+    }
+    locations
+}
+
+impl EndpointConfig {
+    fn resolve(&self, defaults: &Defaults) -> Result<Endpoint> {
+        validate_url(&self.url)?;
+        let kind = Endpoint::new(self.url.clone()).kind();
+        self.check_kind_options(kind)?;
+
+        let method = match &self.method {
+            Some(method) => {
+                let method = method.to_uppercase();
+                if !METHODS.contains(&method.as_str()) {
+                    bail!(
+                        "unsupported method {:?}, expected one of {}",
+                        method,
+                        METHODS.join(", ")
+                    );
+                }
+                Some(method)
+            }
+            None => None,
+        };
+
+        if self.body.is_some() && method.is_none() {
+            bail!("body requires an explicit method");
+        }
+
+        for (name, value) in &self.headers {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("invalid header name {:?}", name))?;
+            HeaderValue::from_str(value)
+                .map_err(|_| anyhow!("invalid value for header {:?}", name))?;
+        }
+
+        if let Some(code) = self
+            .expected_status
+            .iter()
+            .find(|code| !(100..=599).contains(*code))
+        {
+            bail!("expected_status {} is not a valid HTTP status code", code);
+        }
+
+        if let Some(nameserver) = &self.nameserver {
+            parse_nameserver(nameserver)?;
+        }
+
+        let record_type = self.record_type.unwrap_or_default();
+        for record in &self.expected_records {
+            record_type.validate_record(record)?;
+        }
+
+        if kind == EndpointKind::Exec && self.command.first().is_none_or(String::is_empty) {
+            bail!("exec endpoints need a command, e.g. command = [\"./check.sh\"]");
+        }
+
+        if let Some(token) = &self.token {
+            if token.is_empty()
+                || !token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                bail!("token must only contain letters, digits, '-' and '_'");
+            }
+        }
+
+        for (option, pattern) in [
+            ("latency_pattern", &self.latency_pattern),
+            ("metric_pattern", &self.metric_pattern),
+        ] {
+            if let Some(pattern) = pattern {
+                Regex::new(pattern).map_err(|e| anyhow!("invalid {}: {}", option, e))?;
+            }
+        }
+
+        for (option, assertions) in [
+            ("assertions", &self.assertions),
+            ("soft_assertions", &self.soft_assertions),
+        ] {
+            for (i, assertion) in assertions.iter().enumerate() {
+                assertion
+                    .validate()
+                    .map_err(|e| anyhow!("{}[{}]: {}", option, i, e))?;
+            }
+        }
+
+        if self.latency_warn_ms == Some(0) {
+            bail!("latency_warn_ms must be greater than 0");
+        }
+
+        let interval_seconds = self.interval_seconds.unwrap_or(defaults.interval_seconds);
+        if interval_seconds == 0 {
+            bail!("interval_seconds must be greater than 0");
+        }
+
+        let timeout_seconds = self.timeout_seconds.unwrap_or(defaults.timeout_seconds);
+        if timeout_seconds == 0 {
+            bail!("timeout_seconds must be greater than 0");
+        }
+
+        let retry = resolve_retry(
+            &defaults.retry,
+            self.max_retries,
+            self.retry_base_delay_ms,
+            self.retry_max_delay_ms,
+            &self.retry_on,
+        )?;
+
+        if self
+            .name
+            .as_deref()
+            .is_some_and(|name| name.trim().is_empty())
+        {
+            bail!("name must not be empty");
+        }
+
+        Ok(Endpoint {
+            name: self.name.clone(),
+            method,
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            expected_status: self.expected_status.clone(),
+            assertions: self.assertions.clone(),
+            soft_assertions: self.soft_assertions.clone(),
+            send: self.send.clone(),
+            expect: self.expect.clone(),
+            record_type,
+            nameserver: self.nameserver.clone(),
+            expected_records: self.expected_records.clone(),
+            grpc_service: self.grpc_service.clone(),
+            command: self.command.clone(),
+            latency_pattern: self.latency_pattern.clone(),
+            metric_pattern: self.metric_pattern.clone(),
+            token: self.token.clone(),
+            grace_seconds: self
+                .grace_seconds
+                .unwrap_or(DEFAULT_HEARTBEAT_GRACE_SECONDS),
+            timeout_seconds,
+            interval_seconds,
+            retry,
+            cert_warning_days: self.cert_warning_days.unwrap_or(defaults.cert_warning_days),
+            latency_warn_ms: self.latency_warn_ms.or(defaults.latency_warn_ms),
+            tags: self.tags.clone(),
+            paused: self.paused,
+            ..Endpoint::new(self.url.clone())
+        })
+    }
+
+    // options that only make sense for another kind of endpoint are rejected
+    // instead of being silently ignored
+    fn check_kind_options(&self, kind: EndpointKind) -> Result<()> {
+        use EndpointKind::{Dns, Exec, Grpc, Heartbeat, Http, Tcp, WebSocket};
+
+        let options: [(&str, &[EndpointKind], bool); 18] = [
+            ("method", &[Http], self.method.is_some()),
+            ("headers", &[Http, WebSocket], !self.headers.is_empty()),
+            ("body", &[Http], self.body.is_some()),
+            ("expected_status", &[Http], !self.expected_status.is_empty()),
+            ("assertions", &[Http], !self.assertions.is_empty()),
+            ("soft_assertions", &[Http], !self.soft_assertions.is_empty()),
+            ("send", &[Tcp, WebSocket], self.send.is_some()),
+            ("expect", &[Tcp, WebSocket], self.expect.is_some()),
+            ("record_type", &[Dns], self.record_type.is_some()),
+            ("nameserver", &[Dns], self.nameserver.is_some()),
+            (
+                "expected_records",
+                &[Dns],
+                !self.expected_records.is_empty(),
+            ),
+            ("grpc_service", &[Grpc], self.grpc_service.is_some()),
+            ("command", &[Exec], !self.command.is_empty()),
+            ("latency_pattern", &[Exec], self.latency_pattern.is_some()),
+            ("metric_pattern", &[Exec], self.metric_pattern.is_some()),
+            ("token", &[Heartbeat], self.token.is_some()),
+            ("grace_seconds", &[Heartbeat], self.grace_seconds.is_some()),
+            (
+                "latency_warn_ms",
+                &[Http, Tcp, Dns, WebSocket, Grpc, Exec],
+                self.latency_warn_ms.is_some(),
+            ),
+        ];
+
+        if let Some((option, _, _)) = options
+            .iter()
+            .find(|(_, kinds, set)| *set && !kinds.contains(&kind))
+        {
+            bail!(
+                "{} is not supported for {} endpoints",
+                option,
+                kind.as_str()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+fn resolve_retry(
+    defaults: &RetryPolicy,
+    max_retries: Option<u32>,
+    base_delay_ms: Option<u64>,
+    max_delay_ms: Option<u64>,
+    retry_on: &Option<Vec<ErrorKind>>,
+) -> Result<RetryPolicy> {
+    let policy = RetryPolicy {
+        max_retries: max_retries.unwrap_or(defaults.max_retries),
+        base_delay_ms: base_delay_ms.unwrap_or(defaults.base_delay_ms),
+        max_delay_ms: max_delay_ms.unwrap_or(defaults.max_delay_ms),
+        retry_on: retry_on
+            .clone()
+            .unwrap_or_else(|| defaults.retry_on.clone()),
+    };
+
+    if policy.max_delay_ms < policy.base_delay_ms {
+        bail!(
+            "retry_max_delay_ms ({}) must not be lower than retry_base_delay_ms ({})",
+            policy.max_delay_ms,
+            policy.base_delay_ms
+        );
+    }
+
+    Ok(policy)
+}
+
+pub fn validate_url(url: &str) -> Result<()> {
+    if url.trim().is_empty() {
+        bail!("url must not be empty");
+    }
+
+    match url.split_once("://") {
+        None | Some(("http" | "https", _)) => {}
+        Some(("tcp", _)) => {
+            let parsed = Url::parse(url)?;
+            if parsed.host_str().is_none() || parsed.port().is_none() {
+                bail!("tcp endpoints need a host and a port, e.g. tcp://localhost:5432");
+            }
+        }
+        Some(("grpc", _)) => {
+            let parsed = Url::parse(url)?;
+            if parsed.host_str().is_none() || parsed.port().is_none() {
+                bail!("grpc endpoints need a host and a port, e.g. grpc://localhost:50051");
+            }
+        }
+        Some(("ws" | "wss", _)) => {
+            if Url::parse(url)?.host_str().is_none() {
+                bail!("websocket endpoints need a host, e.g. wss://example.com/socket");
+            }
+        }
+        Some(("exec", name)) => {
+            if name.trim_end_matches('/').is_empty() {
+                bail!("exec endpoints need a name, e.g. exec://disk-space");
+            }
+        }
+        Some(("heartbeat", name)) => {
+            if name.trim_end_matches('/').is_empty() {
+                bail!("heartbeat endpoints need a name, e.g. heartbeat://nightly-backup");
+            }
+        }
+        Some(("dns", name)) => {
+            if name.trim_end_matches('/').is_empty() {
+                bail!("dns endpoints need a name to resolve, e.g. dns://example.com");
+            }
+        }
+        Some((scheme, _)) => bail!("unsupported scheme {:?}", scheme),
+    }
+
+    Ok(())
+}
+
+fn check_duplicate(
+    seen: &mut HashMap<String, String>,
+    what: &str,
+    value: &str,
+    location: &str,
+) -> Result<()> {
+    if let Some(first) = seen.insert(value.to_string(), location.to_string()) {
+        bail!(
+            "{}: {} {:?} is already defined by {}",
+            location,
+            what,
+            value,
+            first
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(content: &str) -> Result<Vec<Endpoint>> {
+        toml::from_str::<Config>(content)?.resolve_endpoints()
+    }
+
+    fn error(content: &str) -> String {
+        format!("{:#}", resolve(content).unwrap_err())
+    }
+
+    #[test]
+    fn plain_url_list_gets_the_global_settings() {
+        let endpoints = resolve(
+            "endpoints = [\"https://a.test\", \"tcp://db.test:5432\"]\n\
+             interval_seconds = 30\n\
+             max_retries = 4\n",
+        )
+        .unwrap();
+
+        let urls: Vec<&str> = endpoints.iter().map(|e| e.url.as_str()).collect();
+        assert_eq!(urls, vec!["https://a.test", "tcp://db.test:5432"]);
+        for endpoint in &endpoints {
+            assert_eq!(endpoint.interval_seconds, 30);
+            assert_eq!(endpoint.timeout_seconds, DEFAULT_TIMEOUT_SECONDS);
+            assert_eq!(endpoint.retry.max_retries, 4);
+        }
+    }
+
+    #[test]
+    fn endpoint_tables_inherit_what_they_do_not_set() {
+        let endpoints = resolve(
+            "interval_seconds = 30\n\
+             timeout_seconds = 5\n\
+             cert_warning_days = 21\n\
+             \n\
+             [[endpoint]]\n\
+             url = \"https://api.test/health\"\n\
+             name = \"API\"\n\
+             timeout_seconds = 2\n\
+             \n\
+             [[endpoint]]\n\
+             url = \"https://web.test\"\n\
+             interval_seconds = 300\n",
+        )
+        .unwrap();
+
+        let api = &endpoints[0];
+        assert_eq!(api.name.as_deref(), Some("API"));
+        assert_eq!(api.interval_seconds, 30);
+        assert_eq!(api.timeout_seconds, 2);
+        assert_eq!(api.cert_warning_days, 21);
+
+        let web = &endpoints[1];
+        assert_eq!(web.interval_seconds, 300);
+        assert_eq!(web.timeout_seconds, 5);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(error("intervall_seconds = 30\n").contains("unknown field `intervall_seconds`"));
+        assert!(
+            error("[[endpoint]]\nurl = \"https://a.test\"\ntimeout = 5\n")
+                .contains("unknown field `timeout`")
+        );
+    }
+
+    #[test]
+    fn options_of_another_kind_are_rejected() {
+        assert_eq!(
+            error("[[endpoint]]\nurl = \"tcp://db.test:5432\"\nmethod = \"POST\"\n"),
+            "[[endpoint]] #1 (\"tcp://db.test:5432\"): method is not supported for tcp endpoints"
+        );
+        assert_eq!(
+            error("[[endpoint]]\nurl = \"https://a.test\"\ncommand = [\"true\"]\n"),
+            "[[endpoint]] #1 (\"https://a.test\"): command is not supported for http endpoints"
+        );
+        assert_eq!(
+            error("[[endpoint]]\nurl = \"dns://a.test\"\ntoken = \"abc\"\n"),
+            "[[endpoint]] #1 (\"dns://a.test\"): token is not supported for dns endpoints"
+        );
+    }
+
+    #[test]
+    fn errors_name_the_entry_and_its_url() {
+        assert_eq!(
+            error("endpoints = [\"https://a.test\", \"ftp://files.test\"]\n"),
+            "endpoints[1] (\"ftp://files.test\"): unsupported scheme \"ftp\""
+        );
+        assert_eq!(
+            error(
+                "[[endpoint]]\nurl = \"https://a.test\"\n\
+                 [[endpoint]]\nurl = \"https://b.test\"\nexpected_status = [999]\n"
+            ),
+            "[[endpoint]] #2 (\"https://b.test\"): expected_status 999 is not a valid HTTP status code"
+        );
+        assert_eq!(
+            error("endpoints = [\"https://a.test\"]\n[[endpoint]]\nurl = \"https://a.test\"\n"),
+            "[[endpoint]] #1: url \"https://a.test\" is already defined by endpoints[0]"
+        );
+    }
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("pingme-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(dir.join("home/.config/pingme")).unwrap();
+            Self(dir)
+        }
+
+        fn touch(&self, path: &str) -> PathBuf {
+            let path = self.0.join(path);
+            fs::write(&path, "").unwrap();
+            path
+        }
+
+        fn locations(&self) -> Vec<PathBuf> {
+            default_locations(&self.0, Some(&self.0.join("home/.config")))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn default_locations_are_tried_in_order() {
+        let dir = TempDir::new();
+        assert_eq!(
+            find_config(None, false, None, &dir.locations()).unwrap(),
+            None
+        );
+
+        let user_config = dir.touch("home/.config/pingme/config.toml");
+        assert_eq!(
+            find_config(None, false, None, &dir.locations()).unwrap(),
+            Some(user_config)
+        );
+
+        let pingme_toml = dir.touch("pingme.toml");
+        assert_eq!(
+            find_config(None, false, None, &dir.locations()).unwrap(),
+            Some(pingme_toml)
+        );
+
+        let dot_ping = dir.touch(".ping");
+        assert_eq!(
+            find_config(None, false, None, &dir.locations()).unwrap(),
+            Some(dot_ping)
+        );
+    }
+
+    #[test]
+    fn requested_configs_come_before_the_default_locations() {
+        let dir = TempDir::new();
+        dir.touch(".ping");
+        let from_env = dir.touch("env.toml");
+        let from_arg = dir.touch("arg.toml");
+        let locations = dir.locations();
+
+        assert_eq!(
+            find_config(None, false, Some(from_env.clone()), &locations).unwrap(),
+            Some(from_env.clone())
+        );
+        assert_eq!(
+            find_config(
+                Some(from_arg.clone()),
+                false,
+                Some(from_env.clone()),
+                &locations
+            )
+            .unwrap(),
+            Some(from_arg.clone())
+        );
+        // a url on the command line only gives way to --config
+        assert_eq!(
+            find_config(None, true, Some(from_env), &locations).unwrap(),
+            None
+        );
+        assert_eq!(
+            find_config(Some(from_arg.clone()), true, None, &locations).unwrap(),
+            Some(from_arg)
+        );
+    }
+
+    #[test]
+    fn requested_configs_have_to_exist() {
+        let dir = TempDir::new();
+        dir.touch(".ping");
+        let missing = dir.0.join("missing.toml");
+
+        let error = find_config(None, false, Some(missing.clone()), &dir.locations()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Config file {} given by {} not found",
+                missing.display(),
+                CONFIG_ENV
+            )
+        );
+
+        let error = find_config(Some(missing.clone()), false, None, &dir.locations()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Config file {} given by --config not found",
+                missing.display()
+            )
+        );
+    }
+
+    #[test]
+    fn config_home_falls_back_to_home_unless_xdg_is_absolute() {
+        let home = Some(OsString::from("/home/me"));
+
+        assert_eq!(
+            config_home(Some("/etc/xdg".into()), home.clone()),
+            Some(PathBuf::from("/etc/xdg"))
+        );
+        assert_eq!(
+            config_home(Some("relative".into()), home.clone()),
+            Some(PathBuf::from("/home/me/.config"))
+        );
+        assert_eq!(
+            config_home(None, home),
+            Some(PathBuf::from("/home/me/.config"))
+        );
+        assert_eq!(config_home(None, Some(OsString::new())), None);
+    }
 }
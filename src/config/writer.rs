@@ -0,0 +1,471 @@
+use anyhow::{anyhow, Context, Result};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, Item, Table, Value};
+
+use super::{Config, Defaults};
+use crate::ping::Endpoint;
+
+/// Writes endpoints added, edited or removed in the TUI back to a config
+/// file. The file is re-read for every change and edited in place, so its
+/// comments and formatting are kept.
+pub struct ConfigWriter {
+    path: PathBuf,
+    defaults: Defaults,
+}
+
+#[derive(PartialEq)]
+enum Setting {
+    Text(String),
+    Integer(i64),
+    Flag(bool),
+}
+
+impl From<Setting> for Value {
+    fn from(setting: Setting) -> Self {
+        match setting {
+            Setting::Text(text) => text.into(),
+            Setting::Integer(integer) => integer.into(),
+            Setting::Flag(flag) => flag.into(),
+        }
+    }
+}
+
+/// Where an endpoint is declared in the file.
+enum Entry {
+    Listed(usize),
+    Table(usize),
+}
+
+impl ConfigWriter {
+    pub fn new(path: impl Into<PathBuf>, defaults: Defaults) -> Self {
+        Self {
+            path: path.into(),
+            defaults,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+        self.edit(|document| {
+            if find(document, &endpoint.url).is_none() {
+                self.insert(document, endpoint);
+            }
+            Ok(())
+        })
+    }
+
+    /// Applies the settings of `endpoint` to the entry declared with
+    /// `original_url`, which is added if the file doesn't have it.
+    pub fn update_endpoint(&self, original_url: &str, endpoint: &Endpoint) -> Result<()> {
+        self.edit(|document| {
+            match find(document, original_url) {
+                Some(Entry::Listed(i)) if self.overrides(endpoint).is_empty() => {
+                    endpoints_array(document)?.replace(i, endpoint.url.as_str());
+                }
+                // a plain url can't carry settings, it becomes a table
+                Some(Entry::Listed(i)) => {
+                    remove_url(endpoints_array(document)?, i);
+                    self.insert(document, endpoint);
+                }
+                Some(Entry::Table(i)) => {
+                    let table = endpoint_tables(document)?
+                        .get_mut(i)
+                        .ok_or_else(|| anyhow!("[[endpoint]] #{} disappeared", i + 1))?;
+                    self.update_table(table, endpoint);
+                }
+                None => self.insert(document, endpoint),
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_endpoint(&self, url: &str) -> Result<()> {
+        self.edit(|document| {
+            match find(document, url) {
+                Some(Entry::Listed(i)) => {
+                    remove_url(endpoints_array(document)?, i);
+                }
+                Some(Entry::Table(i)) => endpoint_tables(document)?.remove(i),
+                None => {}
+            }
+            Ok(())
+        })
+    }
+
+    fn edit(&self, change: impl FnOnce(&mut DocumentMut) -> Result<()>) -> Result<()> {
+        let content = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read {}", self.path.display()))?;
+        let mut document: DocumentMut = content
+            .parse()
+            .with_context(|| format!("Failed to parse {}", self.path.display()))?;
+
+        change(&mut document)?;
+
+        // an entry pingme would refuse at the next start is never written
+        let content = document.to_string();
+        toml::from_str::<Config>(&content)
+            .map_err(anyhow::Error::from)
+            .and_then(|config| config.resolve_endpoints())
+            .with_context(|| format!("Not saving to {}", self.path.display()))?;
+
+        write_atomically(&self.path, &content)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    fn insert(&self, document: &mut DocumentMut, endpoint: &Endpoint) {
+        let overrides = self.overrides(endpoint);
+
+        if overrides.is_empty() {
+            if let Some(endpoints) = document
+                .entry("endpoints")
+                .or_insert_with(|| Item::Value(Array::new().into()))
+                .as_array_mut()
+            {
+                push_url(endpoints, &endpoint.url);
+            }
+            return;
+        }
+
+        let mut table = Table::new();
+        table.insert("url", toml_edit::value(endpoint.url.as_str()));
+        for (key, setting) in overrides {
+            table.insert(key, toml_edit::value(setting));
+        }
+        if let Some(tables) = document
+            .entry("endpoint")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+        {
+            tables.push(table);
+        }
+    }
+
+    /// Keys already in the table are updated, or removed when unset. New keys
+    /// are only added where the value differs from the global default.
+    fn update_table(&self, table: &mut Table, endpoint: &Endpoint) {
+        set_value(table, "url", endpoint.url.as_str().into());
+
+        let defaults = settings(&self.defaults.endpoint(endpoint.url.clone()));
+        for ((key, setting), (_, default)) in settings(endpoint).into_iter().zip(defaults) {
+            match (table.contains_key(key), setting) {
+                (true, Some(setting)) => set_value(table, key, setting.into()),
+                (true, None) => {
+                    table.remove(key);
+                }
+                (false, Some(setting)) if Some(&setting) != default.as_ref() => {
+                    table.insert(key, toml_edit::value(setting));
+                }
+                (false, _) => {}
+            }
+        }
+    }
+
+    /// Settings of `endpoint` that differ from the global defaults.
+    fn overrides(&self, endpoint: &Endpoint) -> Vec<(&'static str, Setting)> {
+        let defaults = settings(&self.defaults.endpoint(endpoint.url.clone()));
+        settings(endpoint)
+            .into_iter()
+            .zip(defaults)
+            .filter(|((_, setting), (_, default))| setting != default)
+            .filter_map(|((key, setting), _)| Some((key, setting?)))
+            .collect()
+    }
+}
+
+/// The settings the TUI can change, as config keys.
+fn settings(endpoint: &Endpoint) -> [(&'static str, Option<Setting>); 6] {
+    [
+        ("name", endpoint.name.clone().map(Setting::Text)),
+        (
+            "interval_seconds",
+            Some(Setting::Integer(endpoint.interval_seconds as i64)),
+        ),
+        (
+            "timeout_seconds",
+            Some(Setting::Integer(endpoint.timeout_seconds as i64)),
+        ),
+        (
+            "max_retries",
+            Some(Setting::Integer(endpoint.retry.max_retries as i64)),
+        ),
+        (
+            "latency_warn_ms",
+            endpoint
+                .latency_warn_ms
+                .map(|ms| Setting::Integer(ms as i64)),
+        ),
+        ("paused", endpoint.paused.then_some(Setting::Flag(true))),
+    ]
+}
+
+// lined up with the first url, so a list with one url per line stays that way
+fn push_url(endpoints: &mut Array, url: &str) {
+    let prefix = endpoints
+        .get(0)
+        .and_then(|first| first.decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .filter(|prefix| prefix.contains('\n'))
+        .unwrap_or(" ")
+        .to_string();
+
+    endpoints.push(url);
+    if let Some(pushed) = endpoints.get_mut(endpoints.len() - 1) {
+        pushed.decor_mut().set_prefix(prefix);
+    }
+}
+
+// a comment at the end of a line is stored in front of the next url, so it is
+// handed on to stay with the url it was written after
+fn remove_url(endpoints: &mut Array, i: usize) {
+    let prefix = endpoints
+        .get(i)
+        .and_then(|removed| removed.decor().prefix())
+        .and_then(|prefix| prefix.as_str())
+        .map(str::to_string);
+
+    endpoints.remove(i);
+    let Some(prefix) = prefix else {
+        return;
+    };
+    match endpoints.get_mut(i) {
+        Some(next) => next.decor_mut().set_prefix(prefix),
+        None => {
+            if let Some(end) = prefix.rfind('\n') {
+                endpoints.set_trailing(&prefix[..=end]);
+            }
+        }
+    }
+}
+
+// keeps a comment after the value
+fn set_value(table: &mut Table, key: &str, value: Value) {
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        None => {
+            table.insert(key, Item::Value(value));
+        }
+    }
+}
+
+fn find(document: &DocumentMut, url: &str) -> Option<Entry> {
+    let listed = document
+        .get("endpoints")
+        .and_then(Item::as_array)
+        .and_then(|endpoints| {
+            endpoints
+                .iter()
+                .position(|value| value.as_str() == Some(url))
+        });
+    if let Some(i) = listed {
+        return Some(Entry::Listed(i));
+    }
+
+    document
+        .get("endpoint")
+        .and_then(Item::as_array_of_tables)
+        .and_then(|tables| {
+            tables
+                .iter()
+                .position(|table| table.get("url").and_then(Item::as_str) == Some(url))
+        })
+        .map(Entry::Table)
+}
+
+fn endpoints_array(document: &mut DocumentMut) -> Result<&mut Array> {
+    document
+        .get_mut("endpoints")
+        .and_then(Item::as_array_mut)
+        .ok_or_else(|| anyhow!("endpoints is not a list"))
+}
+
+fn endpoint_tables(document: &mut DocumentMut) -> Result<&mut ArrayOfTables> {
+    document
+        .get_mut("endpoint")
+        .and_then(Item::as_array_of_tables_mut)
+        .ok_or_else(|| anyhow!("endpoint is not a list of tables"))
+}
+
+// written next to the file and renamed over it, so a crash never leaves a
+// half written config behind. A symlinked config has its target replaced,
+// not the link.
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    let tmp_path = path.with_file_name(file_name);
+
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    if let Ok(metadata) = fs::metadata(&path) {
+        fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+
+    fs::rename(&tmp_path, &path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempConfig(PathBuf);
+
+    impl TempConfig {
+        fn new(content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("pingme-{}.toml", uuid::Uuid::new_v4()));
+            fs::write(&path, content).unwrap();
+            Self(path)
+        }
+
+        fn read(&self) -> String {
+            fs::read_to_string(&self.0).unwrap()
+        }
+    }
+
+    impl Drop for TempConfig {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn update_turns_listed_url_into_table_and_keeps_comments() {
+        let config = TempConfig::new(
+            "# endpoints we watch\n\
+             endpoints = [\n    \"https://a.test\", # the api\n    \"https://b.test\",\n]\n\
+             \n\
+             # checked every minute\n\
+             interval_seconds = 60\n",
+        );
+        let defaults = Defaults::default();
+        let writer = ConfigWriter::new(&config.0, defaults.clone());
+
+        let endpoint = Endpoint {
+            name: Some("Backend".to_string()),
+            interval_seconds: 30,
+            ..defaults.endpoint("https://b.test".to_string())
+        };
+        writer.update_endpoint("https://b.test", &endpoint).unwrap();
+
+        assert_eq!(
+            config.read(),
+            "# endpoints we watch\n\
+             endpoints = [\n    \"https://a.test\", # the api\n]\n\
+             \n\
+             # checked every minute\n\
+             interval_seconds = 60\n\
+             \n\
+             [[endpoint]]\n\
+             url = \"https://b.test\"\n\
+             name = \"Backend\"\n\
+             interval_seconds = 30\n"
+        );
+    }
+
+    #[test]
+    fn update_keeps_comments_in_endpoint_tables() {
+        let config = TempConfig::new(
+            "[[endpoint]]\n\
+             url = \"https://a.test\"\n\
+             # slow on purpose\n\
+             timeout_seconds = 30 # seconds\n",
+        );
+        let defaults = Defaults::default();
+        let writer = ConfigWriter::new(&config.0, defaults.clone());
+
+        let endpoint = Endpoint {
+            timeout_seconds: 45,
+            ..defaults.endpoint("https://a.test".to_string())
+        };
+        writer.update_endpoint("https://a.test", &endpoint).unwrap();
+
+        assert_eq!(
+            config.read(),
+            "[[endpoint]]\n\
+             url = \"https://a.test\"\n\
+             # slow on purpose\n\
+             timeout_seconds = 45 # seconds\n"
+        );
+    }
+
+    #[test]
+    fn remove_keeps_comments_of_the_other_urls() {
+        let config = TempConfig::new(
+            "endpoints = [\n    \"https://a.test\", # A\n    \"https://b.test\", # B\n    \"https://c.test\", # C\n]\n",
+        );
+        let writer = ConfigWriter::new(&config.0, Defaults::default());
+
+        writer.remove_endpoint("https://b.test").unwrap();
+        assert_eq!(
+            config.read(),
+            "endpoints = [\n    \"https://a.test\", # A\n    \"https://c.test\", # C\n]\n"
+        );
+
+        writer.remove_endpoint("https://c.test").unwrap();
+        assert_eq!(
+            config.read(),
+            "endpoints = [\n    \"https://a.test\", # A\n]\n"
+        );
+
+        let inline = TempConfig::new("endpoints = [\"https://a.test\", \"https://b.test\"]\n");
+        let writer = ConfigWriter::new(&inline.0, Defaults::default());
+        writer.remove_endpoint("https://a.test").unwrap();
+        assert_eq!(inline.read(), "endpoints = [\"https://b.test\"]\n");
+    }
+
+    #[test]
+    fn changes_pingme_would_reject_are_not_written() {
+        let original = "endpoints = [\"https://a.test\"]\n";
+        let config = TempConfig::new(original);
+        let defaults = Defaults::default();
+        let writer = ConfigWriter::new(&config.0, defaults.clone());
+
+        let heartbeat = Endpoint {
+            latency_warn_ms: Some(500),
+            ..defaults.endpoint("heartbeat://backup".to_string())
+        };
+        let error = writer.add_endpoint(&heartbeat).unwrap_err();
+
+        assert!(
+            format!("{:#}", error).contains("latency_warn_ms is not supported"),
+            "{:#}",
+            error
+        );
+        assert_eq!(config.read(), original);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_config_is_written_through_the_link() {
+        let config = TempConfig::new("endpoints = [\"https://a.test\"]\n");
+        let link = TempConfig(config.0.with_extension("link.toml"));
+        std::os::unix::fs::symlink(&config.0, &link.0).unwrap();
+        let defaults = Defaults::default();
+        let writer = ConfigWriter::new(&link.0, defaults.clone());
+
+        writer
+            .add_endpoint(&defaults.endpoint("https://b.test".to_string()))
+            .unwrap();
+
+        assert!(fs::symlink_metadata(&link.0)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            config.read(),
+            "endpoints = [\"https://a.test\", \"https://b.test\"]\n"
+        );
+    }
+}
@@ -1,19 +1,20 @@
-use anyhow::Result;
-use app::{App, InputMode, LogEntry, LogLevel};
-use clap::{Arg, Command};
+use anyhow::{bail, Context, Result};
+use app::{App, EditForm, InputMode, LogEntry, LogLevel};
+use clap::{Arg, ArgAction, Command};
 use crossterm::{event, execute, terminal::enable_raw_mode};
 use ratatui::prelude::CrosstermBackend;
 
-use anyhow;
 use crossterm::{
     event::{DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
     terminal::{disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::Terminal;
-use std::{fs, io, path::Path, time::Duration as StdDuration};
+use std::{
+    io,
+    time::{Duration as StdDuration, Instant},
+};
 use tokio::sync::mpsc;
 use tui_textarea::{Input, TextArea};
-use uuid::Uuid;
 
 mod app;
 mod config;
@@ -22,13 +23,22 @@ mod storage;
 mod ui;
 mod visitor;
 
-use config::Config;
-use ping::{Endpoint, PingManager, PingResult};
+use config::{Config, ConfigWriter, Defaults};
+use ping::{ConcurrencyLimits, Endpoint, HeartbeatServer, PingManager, PingResult};
+use storage::RetentionPolicy;
+use visitor::StorageVisitor;
+
+/// Stats are rebuilt when results arrive or a key is pressed, and otherwise
+/// at this pace so the time windows keep moving.
+const STATS_REFRESH: StdDuration = StdDuration::from_secs(1);
+
+/// How often results and rollups past their retention are dropped.
+const RETENTION_INTERVAL: StdDuration = StdDuration::from_secs(10 * 60);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     if let Err(e) = run_app().await {
-        eprintln!("Error: {}", e);
+        eprintln!("Error: {:#}", e);
         std::process::exit(1);
     }
     Ok(())
@@ -45,39 +55,128 @@ async fn run_app() -> Result<()> {
                 .value_name("FILE")
                 .help("Configuration file"),
         )
+        .arg(
+            Arg::new("db")
+                .long("db")
+                .value_name("FILE")
+                .help("SQLite database for persistent history"),
+        )
+        .arg(
+            Arg::new("prune")
+                .long("prune")
+                .action(ArgAction::SetTrue)
+                .help("Remove endpoints that are no longer in the config, and their history"),
+        )
+        .arg(
+            Arg::new("save")
+                .long("save")
+                .action(ArgAction::SetTrue)
+                .help(
+                    "Write endpoints added, edited or removed in the TUI back to the config file",
+                ),
+        )
         .get_matches();
 
-    let mut ping_manager: PingManager = PingManager::new(60);
+    let storage = match matches.get_one::<String>("db") {
+        Some(db_path) => StorageVisitor::sqlite(db_path).await?,
+        None => StorageVisitor::new(),
+    };
 
-    if let Some(config_path) = matches.get_one::<String>("config") {
-        if Path::new(config_path).exists() {
-            let config_content = fs::read_to_string(config_path)?;
-            let config: Config = toml::from_str(&config_content)?;
+    let mut defaults = Defaults::default();
+    let mut limits = ConcurrencyLimits::default();
+    let mut retention = RetentionPolicy::default();
+    let mut heartbeat_listen = None;
+    let mut endpoints = Vec::new();
+    let url = matches.get_one::<String>("url");
+    let config_path = config::locate(
+        matches.get_one::<String>("config").map(String::as_str),
+        url.is_some(),
+    )?;
 
-            for url in config.endpoints {
-                ping_manager.add_endpoint(url)?;
-            }
-        }
-    } else if let Some(url) = matches.get_one::<String>("url") {
-        ping_manager.add_endpoint(url.clone())?;
-    } else if Path::new(".ping").exists() {
-        let config_content = fs::read_to_string(".ping")?;
-        let config: Config = toml::from_str(&config_content)?;
+    if let Some(path) = &config_path {
+        let config = Config::load(path)?;
+        (defaults, limits, retention, heartbeat_listen, endpoints) = (|| {
+            anyhow::Ok((
+                config.defaults()?,
+                config.concurrency_limits()?,
+                config.retention()?,
+                config.heartbeat_listen()?,
+                config.resolve_endpoints()?,
+            ))
+        })()
+        .with_context(|| format!("Invalid config {}", path.display()))?;
+    } else if let Some(url) = url {
+        endpoints.push(defaults.endpoint(url.clone()));
+    }
+
+    let config_writer = match (matches.get_flag("save"), &config_path) {
+        (true, Some(path)) => Some(ConfigWriter::new(path, defaults.clone())),
+        (true, None) => bail!(
+            "--save needs a config file, pass one with --config or {}",
+            config::CONFIG_ENV
+        ),
+        (false, _) => None,
+    };
+    let prune = matches.get_flag("prune");
+    if prune && config_path.is_none() {
+        bail!(
+            "--prune needs a config file, pass one with --config or {}",
+            config::CONFIG_ENV
+        );
+    }
 
-        for url in config.endpoints {
-            ping_manager.add_endpoint(url)?;
+    let mut ping_manager: PingManager = PingManager::new(storage, limits);
+
+    // the config decides what is monitored, also over endpoints kept with --db
+    let retired = match &config_path {
+        Some(_) => {
+            ping_manager
+                .retire_endpoints_except(&endpoints, prune)
+                .await?
         }
+        None => Vec::new(),
+    };
+
+    for endpoint in endpoints {
+        ping_manager.add_endpoint(endpoint).await?;
+    }
+
+    let (result_sender, mut result_receiver) = mpsc::unbounded_channel::<PingResult>();
+    let (log_sender, mut log_receiver) = mpsc::unbounded_channel::<LogEntry>();
+
+    // bound before the terminal is taken over so a busy port is reported plainly
+    if let Some(address) = heartbeat_listen {
+        let heartbeat_server = HeartbeatServer::bind(
+            address,
+            ping_manager.get_storage(),
+            result_sender.clone(),
+            log_sender.clone(),
+        )?;
+        tokio::spawn(heartbeat_server.run());
     }
 
+    let retention_storage = ping_manager.get_storage();
+    let retention_log = log_sender.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = retention_storage.apply_retention(&retention).await {
+                let _ = retention_log.send(LogEntry {
+                    timestamp: chrono::Utc::now(),
+                    level: LogLevel::Error,
+                    message: format!("Failed to apply retention: {}", e),
+                });
+            }
+        }
+    });
+
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let (result_sender, mut result_receiver) = mpsc::unbounded_channel::<PingResult>();
-    let (log_sender, mut log_receiver) = mpsc::unbounded_channel::<LogEntry>();
-
     let polling_manager = ping_manager.clone();
     tokio::spawn(async move {
         polling_manager
@@ -89,18 +188,37 @@ async fn run_app() -> Result<()> {
     let storage = ping_manager.get_storage();
 
     app.add_log(LogLevel::Info, "Application started".to_string());
+    for endpoint in retired {
+        let message = if prune {
+            format!(
+                "Removed {} and its history, it is no longer in the config",
+                endpoint.display_name()
+            )
+        } else {
+            format!(
+                "Paused {}, it is no longer in the config. Its history is kept, --prune removes it",
+                endpoint.display_name()
+            )
+        };
+        app.add_log(LogLevel::Info, message);
+    }
+    if let Some(writer) = &config_writer {
+        app.add_log(
+            LogLevel::Info,
+            format!("Saving endpoint changes to {}", writer.path().display()),
+        );
+    }
 
-    loop {
-        while let Ok(result) = result_receiver.try_recv() {
-            if let Err(e) = storage.save_result(&result) {
-                eprintln!("Error saving result: {}", e);
-            }
+    let mut last_refresh: Option<Instant> = None;
 
-            app.add_realtime_block(result.endpoint_id, result.status);
+    loop {
+        let mut refresh = last_refresh.is_none_or(|at| at.elapsed() >= STATS_REFRESH);
 
-            if let Err(e) = app.update_stats(&storage) {
-                eprintln!("Error updating status {}", e)
+        while let Ok(result) = result_receiver.try_recv() {
+            if let Err(e) = storage.save_result(&result).await {
+                app.add_log(LogLevel::Error, format!("Error saving result: {}", e));
             }
+            refresh = true;
         }
 
         while let Ok(log_entry) = log_receiver.try_recv() {
@@ -110,27 +228,65 @@ async fn run_app() -> Result<()> {
             }
         }
 
-        app.update_stats(&storage)?;
+        if refresh {
+            app.update_stats(&storage).await?;
+            last_refresh = Some(Instant::now());
+        }
 
         terminal.draw(|f| ui::ui(f, &app))?;
 
         if event::poll(StdDuration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
+                    // selection and range changes show up on the next frame
+                    last_refresh = None;
                     match app.input_mode {
                         InputMode::Normal => {
                             if app.developer_mode {
                                 handle_developer_mode_input(&mut app, key.code);
                             } else {
-                                if handle_normal_mode_input(&mut app, &storage, key.code)? {
+                                if handle_normal_mode_input(
+                                    &mut app,
+                                    &storage,
+                                    config_writer.as_ref(),
+                                    key.code,
+                                )
+                                .await?
+                                {
                                     break;
                                 }
                             }
                         }
                         InputMode::Adding => {
-                            if handle_adding_mode_input(&mut app, &storage, key, &Event::Key(key))?
-                            {
-                            }
+                            if handle_adding_mode_input(
+                                &mut app,
+                                &storage,
+                                config_writer.as_ref(),
+                                &defaults,
+                                key,
+                                &Event::Key(key),
+                            )
+                            .await?
+                            {}
+                        }
+                        InputMode::Editing => {
+                            handle_editing_mode_input(
+                                &mut app,
+                                &storage,
+                                config_writer.as_ref(),
+                                key,
+                                &Event::Key(key),
+                            )
+                            .await?;
+                        }
+                        InputMode::ConfirmingRemoval => {
+                            handle_removal_confirmation(
+                                &mut app,
+                                &storage,
+                                config_writer.as_ref(),
+                                key.code,
+                            )
+                            .await?;
                         }
                     }
                 }
@@ -149,9 +305,10 @@ async fn run_app() -> Result<()> {
     Ok(())
 }
 
-fn handle_normal_mode_input(
+async fn handle_normal_mode_input(
     app: &mut App,
-    storage: &crate::visitor::StorageVisitor,
+    storage: &StorageVisitor,
+    config_writer: Option<&ConfigWriter>,
     key_code: KeyCode,
 ) -> Result<bool> {
     match key_code {
@@ -159,6 +316,12 @@ fn handle_normal_mode_input(
             app.add_log(LogLevel::Info, "Exiting application".to_string());
             return Ok(true);
         }
+        KeyCode::Enter => {
+            app.show_details = !app.show_details;
+        }
+        KeyCode::Esc => {
+            app.show_details = false;
+        }
         KeyCode::Char('a') => {
             app.input_mode = InputMode::Adding;
             app.url_input = TextArea::default();
@@ -168,6 +331,48 @@ fn handle_normal_mode_input(
             app.toggle_developer_mode();
             app.add_log(LogLevel::Info, "Switched to developer mode".to_string());
         }
+        KeyCode::Char('l') => {
+            app.toggle_latency_chart();
+        }
+        KeyCode::Char('e') => {
+            if let Some(endpoint) = app.selected_endpoint() {
+                app.edit_form = Some(EditForm::new(endpoint.clone()));
+                app.input_mode = InputMode::Editing;
+            }
+        }
+        KeyCode::Char('x') | KeyCode::Delete if app.selected_endpoint().is_some() => {
+            app.input_mode = InputMode::ConfirmingRemoval;
+        }
+        KeyCode::Char('p') => {
+            if let Some(endpoint) = app.selected_endpoint() {
+                let endpoint = Endpoint {
+                    paused: !endpoint.paused,
+                    ..endpoint.clone()
+                };
+                let action = if endpoint.paused { "Paused" } else { "Resumed" };
+                match storage.add_endpoint(&endpoint).await {
+                    Ok(_) => {
+                        app.add_log(
+                            LogLevel::Info,
+                            format!("{} {}", action, endpoint.display_name()),
+                        );
+                        save_to_config(app, config_writer, |writer| {
+                            writer.update_endpoint(&endpoint.url, &endpoint)
+                        });
+                    }
+                    Err(e) => app.add_log(
+                        LogLevel::Error,
+                        format!("Failed to update {}: {}", endpoint.display_name(), e),
+                    ),
+                }
+            }
+        }
+        KeyCode::Char('t') => {
+            app.next_time_range();
+        }
+        KeyCode::Char('T') => {
+            app.previous_time_range();
+        }
         KeyCode::Down | KeyCode::Char('j') => {
             app.next_endpoint();
         }
@@ -176,7 +381,7 @@ fn handle_normal_mode_input(
         }
         KeyCode::Char('r') => {
             app.add_log(LogLevel::Info, "Refreshing data...".to_string());
-            if let Err(e) = app.update_stats(storage) {
+            if let Err(e) = app.update_stats(storage).await {
                 app.add_log(LogLevel::Error, format!("Failed to refresh: {}", e));
             } else {
                 app.add_log(LogLevel::Success, "Data refreshed successfully".to_string());
@@ -207,23 +412,27 @@ fn handle_developer_mode_input(app: &mut App, key_code: KeyCode) {
     }
 }
 
-fn handle_adding_mode_input(
+async fn handle_adding_mode_input(
     app: &mut App,
-    storage: &crate::visitor::StorageVisitor,
+    storage: &StorageVisitor,
+    config_writer: Option<&ConfigWriter>,
+    defaults: &Defaults,
     key: crossterm::event::KeyEvent,
     event: &Event,
 ) -> Result<bool> {
     match key.code {
         KeyCode::Enter => {
             let url = app.url_input.lines().join("");
-            if !url.is_empty() {
-                let endpoint = Endpoint {
-                    id: Uuid::new_v4(),
-                    url: url.clone(),
-                };
-                match storage.add_endpoint(&endpoint) {
+            if let Err(e) = config::validate_url(&url) {
+                if !url.is_empty() {
+                    app.add_log(LogLevel::Error, format!("Invalid URL {}: {}", url, e));
+                }
+            } else {
+                let endpoint = defaults.endpoint(url.clone());
+                match storage.add_endpoint(&endpoint).await {
                     Ok(_) => {
                         app.add_log(LogLevel::Success, format!("Added endpoint: {}", url));
+                        save_to_config(app, config_writer, |writer| writer.add_endpoint(&endpoint));
                     }
                     Err(e) => {
                         app.add_log(
@@ -245,3 +454,106 @@ fn handle_adding_mode_input(
     }
     Ok(false)
 }
+
+async fn handle_editing_mode_input(
+    app: &mut App,
+    storage: &StorageVisitor,
+    config_writer: Option<&ConfigWriter>,
+    key: crossterm::event::KeyEvent,
+    event: &Event,
+) -> Result<()> {
+    let Some(form) = app.edit_form.as_mut() else {
+        app.input_mode = InputMode::Normal;
+        return Ok(());
+    };
+
+    match key.code {
+        KeyCode::Esc => {
+            app.edit_form = None;
+            app.input_mode = InputMode::Normal;
+            app.add_log(LogLevel::Info, "Cancelled edit".to_string());
+        }
+        KeyCode::Tab | KeyCode::Down => form.next_field(),
+        KeyCode::BackTab | KeyCode::Up => form.previous_field(),
+        KeyCode::Enter => {
+            let original_url = form.endpoint.url.clone();
+            let endpoint = match form.apply() {
+                Ok(endpoint) => endpoint,
+                Err(e) => {
+                    form.error = Some(e.to_string());
+                    return Ok(());
+                }
+            };
+            if app
+                .endpoints_stats
+                .iter()
+                .any(|stats| stats.endpoint.id != endpoint.id && stats.endpoint.url == endpoint.url)
+            {
+                form.error = Some(format!("{} is already monitored", endpoint.url));
+                return Ok(());
+            }
+
+            match storage.add_endpoint(&endpoint).await {
+                Ok(_) => {
+                    app.add_log(
+                        LogLevel::Success,
+                        format!("Updated endpoint: {}", endpoint.display_name()),
+                    );
+                    save_to_config(app, config_writer, |writer| {
+                        writer.update_endpoint(&original_url, &endpoint)
+                    });
+                    app.edit_form = None;
+                    app.input_mode = InputMode::Normal;
+                }
+                Err(e) => form.error = Some(format!("Failed to save: {}", e)),
+            }
+        }
+        _ => {
+            form.focused_input().input(Input::from(event.clone()));
+        }
+    }
+    Ok(())
+}
+
+async fn handle_removal_confirmation(
+    app: &mut App,
+    storage: &StorageVisitor,
+    config_writer: Option<&ConfigWriter>,
+    key_code: KeyCode,
+) -> Result<()> {
+    if let (KeyCode::Char('y'), Some(endpoint)) = (key_code, app.selected_endpoint()) {
+        let endpoint = endpoint.clone();
+        match storage.remove_endpoint(endpoint.id).await {
+            Ok(_) => {
+                app.add_log(
+                    LogLevel::Success,
+                    format!("Removed endpoint: {}", endpoint.display_name()),
+                );
+                save_to_config(app, config_writer, |writer| {
+                    writer.remove_endpoint(&endpoint.url)
+                });
+                app.show_details = false;
+            }
+            Err(e) => app.add_log(
+                LogLevel::Error,
+                format!("Failed to remove {}: {}", endpoint.display_name(), e),
+            ),
+        }
+    }
+
+    app.input_mode = InputMode::Normal;
+    Ok(())
+}
+
+/// Mirrors a change into the config file when running with `--save`.
+fn save_to_config(
+    app: &mut App,
+    config_writer: Option<&ConfigWriter>,
+    save: impl FnOnce(&ConfigWriter) -> Result<()>,
+) {
+    if let Some(writer) = config_writer {
+        if let Err(e) = save(writer) {
+            app.add_log(LogLevel::Error, format!("{:#}", e));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A check run against the response of an HTTP endpoint. Configured as e.g.
+/// `{ type = "json_equals", pointer = "/status", value = "ok" }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Assertion {
+    Contains { value: String },
+    NotContains { value: String },
+    Regex { pattern: String },
+    JsonEquals { pointer: String, value: Value },
+    JsonExists { pointer: String },
+    HeaderExists { name: String },
+    HeaderEquals { name: String, value: String },
+    HeaderContains { name: String, value: String },
+}
+
+impl Assertion {
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Assertion::Regex { pattern } => {
+                Regex::new(pattern).map_err(|e| anyhow!("invalid regex {:?}: {}", pattern, e))?;
+            }
+            Assertion::JsonEquals { pointer, .. } | Assertion::JsonExists { pointer }
+                if !pointer.is_empty() && !pointer.starts_with('/') =>
+            {
+                bail!("json pointer {:?} must be empty or start with '/'", pointer);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    pub fn needs_body(&self) -> bool {
+        !matches!(
+            self,
+            Assertion::HeaderExists { .. }
+                | Assertion::HeaderEquals { .. }
+                | Assertion::HeaderContains { .. }
+        )
+    }
+
+    /// Returns a description of the failure when the response does not satisfy
+    /// the assertion.
+    pub fn check(&self, headers: &HeaderMap, body: &str) -> Result<(), String> {
+        match self {
+            Assertion::Contains { value } => {
+                if !body.contains(value.as_str()) {
+                    return Err(format!("body does not contain {:?}", value));
+                }
+            }
+            Assertion::NotContains { value } => {
+                if body.contains(value.as_str()) {
+                    return Err(format!("body contains {:?}", value));
+                }
+            }
+            Assertion::Regex { pattern } => {
+                let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                if !regex.is_match(body) {
+                    return Err(format!("body does not match /{}/", pattern));
+                }
+            }
+            Assertion::JsonEquals { pointer, value } => {
+                let json = parse_json(body)?;
+                match json.pointer(pointer) {
+                    Some(actual) if actual == value => {}
+                    Some(actual) => {
+                        return Err(format!("{} is {}, expected {}", pointer, actual, value));
+                    }
+                    None => return Err(format!("{} not found in body", pointer)),
+                }
+            }
+            Assertion::JsonExists { pointer } => {
+                if parse_json(body)?.pointer(pointer).is_none() {
+                    return Err(format!("{} not found in body", pointer));
+                }
+            }
+            Assertion::HeaderExists { name } => {
+                header(headers, name)?;
+            }
+            Assertion::HeaderEquals { name, value } => {
+                let actual = header(headers, name)?;
+                if actual != *value {
+                    return Err(format!(
+                        "header {} is {:?}, expected {:?}",
+                        name, actual, value
+                    ));
+                }
+            }
+            Assertion::HeaderContains { name, value } => {
+                let actual = header(headers, name)?;
+                if !actual.contains(value.as_str()) {
+                    return Err(format!(
+                        "header {} is {:?}, expected it to contain {:?}",
+                        name, actual, value
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_json(body: &str) -> Result<Value, String> {
+    serde_json::from_str(body).map_err(|e| format!("body is not valid JSON: {}", e))
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Result<String, String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+        .ok_or_else(|| format!("header {} is missing", name))
+}
@@ -0,0 +1,67 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+const DEFAULT_DNS_PORT: u16 = 53;
+
+/// Record type queried by a `dns://` endpoint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    #[default]
+    A,
+    Aaaa,
+    Cname,
+    Txt,
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+            RecordType::Cname => "CNAME",
+            RecordType::Txt => "TXT",
+        }
+    }
+
+    /// Brings a record into the form answers are compared in, so that e.g.
+    /// `2001:db8::1` matches `2001:0db8:0:0:0:0:0:1` and `Example.com.` matches
+    /// `example.com`.
+    pub fn normalize(&self, record: &str) -> String {
+        match self {
+            RecordType::A | RecordType::Aaaa => record
+                .parse::<IpAddr>()
+                .map_or_else(|_| record.to_string(), |ip| ip.to_string()),
+            RecordType::Cname => record.trim_end_matches('.').to_lowercase(),
+            RecordType::Txt => record.to_string(),
+        }
+    }
+
+    pub fn validate_record(&self, record: &str) -> Result<()> {
+        let valid = match self {
+            RecordType::A => record.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv4()),
+            RecordType::Aaaa => record.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv6()),
+            RecordType::Cname => !record.trim().is_empty(),
+            RecordType::Txt => true,
+        };
+
+        if !valid {
+            bail!("{:?} is not a valid {} record", record, self.as_str());
+        }
+        Ok(())
+    }
+}
+
+/// Parses `ip` or `ip:port`, defaulting to port 53.
+pub fn parse_nameserver(nameserver: &str) -> Result<SocketAddr> {
+    if let Ok(ip) = nameserver.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, DEFAULT_DNS_PORT));
+    }
+    nameserver.parse().map_err(|_| {
+        anyhow!(
+            "invalid nameserver {:?}, expected ip or ip:port",
+            nameserver
+        )
+    })
+}
@@ -0,0 +1,327 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use hyper::{
+    server::{conn::AddrIncoming, Builder},
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration as StdDuration,
+};
+use tokio::{sync::mpsc, time::Instant};
+use uuid::Uuid;
+
+use super::{Endpoint, EndpointKind, ErrorKind, PingResult};
+use crate::app::{LogEntry, LogLevel};
+use crate::visitor::StorageVisitor;
+
+pub const HEARTBEAT_PATH: &str = "/heartbeat/";
+
+// how often heartbeat deadlines are checked
+const WATCH_INTERVAL: StdDuration = StdDuration::from_secs(1);
+
+#[derive(Clone)]
+struct Heartbeats {
+    storage: StorageVisitor,
+    // when each heartbeat endpoint is reported down unless a ping arrives first
+    deadlines: Arc<Mutex<HashMap<Uuid, Instant>>>,
+    result_sender: mpsc::UnboundedSender<PingResult>,
+    log_sender: mpsc::UnboundedSender<LogEntry>,
+}
+
+/// Local HTTP listener for `heartbeat://` endpoints. Every ping to
+/// `/heartbeat/<token>` counts as up. An endpoint that goes `interval_seconds`
+/// plus `grace_seconds` without one is reported down, and again every
+/// interval after that until pings resume.
+pub struct HeartbeatServer {
+    address: SocketAddr,
+    builder: Builder<AddrIncoming>,
+    heartbeats: Heartbeats,
+}
+
+impl HeartbeatServer {
+    pub fn bind(
+        address: SocketAddr,
+        storage: StorageVisitor,
+        result_sender: mpsc::UnboundedSender<PingResult>,
+        log_sender: mpsc::UnboundedSender<LogEntry>,
+    ) -> Result<Self> {
+        let incoming = AddrIncoming::bind(&address)
+            .with_context(|| format!("Failed to listen for heartbeats on {}", address))?;
+
+        Ok(Self {
+            // the port that was picked when port 0 was asked for
+            address: incoming.local_addr(),
+            builder: Server::builder(incoming),
+            heartbeats: Heartbeats {
+                storage,
+                deadlines: Arc::new(Mutex::new(HashMap::new())),
+                result_sender,
+                log_sender,
+            },
+        })
+    }
+
+    pub async fn run(self) {
+        let heartbeats = self.heartbeats;
+
+        if let Ok(endpoints) = heartbeats.storage.get_endpoints().await {
+            for endpoint in endpoints
+                .iter()
+                .filter(|endpoint| endpoint.kind() == EndpointKind::Heartbeat)
+            {
+                heartbeats.log(
+                    LogLevel::Info,
+                    format!(
+                        "{} - expecting heartbeats at http://{}{}{}",
+                        endpoint.display_name(),
+                        self.address,
+                        HEARTBEAT_PATH,
+                        endpoint.heartbeat_token()
+                    ),
+                );
+            }
+        }
+
+        tokio::spawn(heartbeats.clone().watch());
+
+        let service_heartbeats = heartbeats.clone();
+        let make_service = make_service_fn(move |_| {
+            let heartbeats = service_heartbeats.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let heartbeats = heartbeats.clone();
+                    async move { Ok::<_, Infallible>(heartbeats.handle(request).await) }
+                }))
+            }
+        });
+
+        if let Err(e) = self.builder.serve(make_service).await {
+            heartbeats.log(LogLevel::Error, format!("Heartbeat server stopped: {}", e));
+        }
+    }
+}
+
+impl Heartbeats {
+    fn log(&self, level: LogLevel, message: String) {
+        let _ = self.log_sender.send(LogEntry {
+            timestamp: Utc::now(),
+            level,
+            message,
+        });
+    }
+
+    async fn handle(&self, request: Request<Body>) -> Response<Body> {
+        let Some(token) = request
+            .uri()
+            .path()
+            .strip_prefix(HEARTBEAT_PATH)
+            .map(|token| token.trim_end_matches('/'))
+        else {
+            return reply(StatusCode::NOT_FOUND, "not found\n");
+        };
+
+        let endpoints = match self.storage.get_endpoints().await {
+            Ok(endpoints) => endpoints,
+            Err(e) => {
+                self.log(
+                    LogLevel::Error,
+                    format!("Failed to look up heartbeat endpoints: {}", e),
+                );
+                return reply(StatusCode::INTERNAL_SERVER_ERROR, "storage error\n");
+            }
+        };
+
+        let Some(endpoint) = endpoints.iter().find(|endpoint| {
+            endpoint.kind() == EndpointKind::Heartbeat && endpoint.heartbeat_token() == token
+        }) else {
+            return reply(StatusCode::NOT_FOUND, "unknown heartbeat token\n");
+        };
+
+        if endpoint.paused {
+            return reply(StatusCode::OK, "paused\n");
+        }
+
+        self.deadlines
+            .lock()
+            .unwrap()
+            .insert(endpoint.id, Instant::now() + allowed_silence(endpoint));
+
+        let _ = self.result_sender.send(PingResult::up(endpoint.id, 0));
+        self.log(
+            LogLevel::Success,
+            format!("{} - heartbeat received", endpoint.display_name()),
+        );
+
+        reply(StatusCode::OK, "OK\n")
+    }
+
+    async fn watch(self) {
+        let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+
+        loop {
+            ticker.tick().await;
+
+            let endpoints: Vec<Endpoint> = match self.storage.get_endpoints().await {
+                Ok(endpoints) => endpoints
+                    .into_iter()
+                    .filter(|endpoint| {
+                        endpoint.kind() == EndpointKind::Heartbeat && !endpoint.paused
+                    })
+                    .collect(),
+                Err(_) => continue,
+            };
+
+            let now = Instant::now();
+            let mut missed = Vec::new();
+            {
+                let mut deadlines = self.deadlines.lock().unwrap();
+                deadlines.retain(|id, _| endpoints.iter().any(|endpoint| endpoint.id == *id));
+
+                for endpoint in &endpoints {
+                    // a new endpoint gets a full period before its first heartbeat is due
+                    let deadline = deadlines
+                        .entry(endpoint.id)
+                        .or_insert_with(|| now + allowed_silence(endpoint));
+
+                    if *deadline <= now {
+                        *deadline = now + StdDuration::from_secs(endpoint.interval_seconds);
+                        missed.push(endpoint);
+                    }
+                }
+            }
+
+            for endpoint in missed {
+                let message = format!(
+                    "no heartbeat within {}s",
+                    allowed_silence(endpoint).as_secs()
+                );
+                self.log(
+                    LogLevel::Error,
+                    format!("{} - DOWN: {}", endpoint.display_name(), message),
+                );
+                let _ = self.result_sender.send(PingResult::down(
+                    endpoint.id,
+                    0,
+                    ErrorKind::Timeout,
+                    message,
+                ));
+            }
+        }
+    }
+}
+
+fn allowed_silence(endpoint: &Endpoint) -> StdDuration {
+    StdDuration::from_secs(endpoint.interval_seconds + endpoint.grace_seconds)
+}
+
+fn reply(status: StatusCode, body: &'static str) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        time::timeout,
+    };
+
+    use crate::ping::Status;
+
+    async fn start(endpoint: &Endpoint) -> (SocketAddr, mpsc::UnboundedReceiver<PingResult>) {
+        let storage = StorageVisitor::new();
+        storage.add_endpoint(endpoint).await.unwrap();
+        let (result_sender, results) = mpsc::unbounded_channel();
+        let (log_sender, _) = mpsc::unbounded_channel();
+
+        let server = HeartbeatServer::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            storage,
+            result_sender,
+            log_sender,
+        )
+        .unwrap();
+        let address = server.address;
+        tokio::spawn(server.run());
+
+        (address, results)
+    }
+
+    /// Sends a ping and returns the raw response.
+    async fn ping(address: SocketAddr, token: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        let request = format!(
+            "GET {}{} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            HEARTBEAT_PATH, token
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn heartbeat(interval_seconds: u64) -> Endpoint {
+        Endpoint {
+            token: Some("nightly-backup".to_string()),
+            interval_seconds,
+            grace_seconds: 0,
+            ..Endpoint::new("heartbeat://backup".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn ping_on_time_reports_the_endpoint_up() {
+        let endpoint = heartbeat(3600);
+        let (address, mut results) = start(&endpoint).await;
+
+        let response = ping(address, "nightly-backup").await;
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        let result = results.recv().await.unwrap();
+        assert_eq!(result.endpoint_id, endpoint.id);
+        assert_eq!(result.status, Status::Up);
+    }
+
+    #[tokio::test]
+    async fn unknown_token_is_refused() {
+        let (address, mut results) = start(&heartbeat(3600)).await;
+
+        let response = ping(address, "someone-else").await;
+
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.ends_with("unknown heartbeat token\n"),
+            "{}",
+            response
+        );
+        assert!(results.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn missed_deadline_reports_the_endpoint_down() {
+        let endpoint = heartbeat(1);
+        let (_, mut results) = start(&endpoint).await;
+
+        let result = timeout(StdDuration::from_secs(5), results.recv())
+            .await
+            .expect("no result after the deadline passed")
+            .unwrap();
+
+        assert_eq!(result.endpoint_id, endpoint.id);
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Timeout));
+        assert_eq!(result.message.as_deref(), Some("no heartbeat within 1s"));
+    }
+}
@@ -1,55 +1,491 @@
 use anyhow::Result;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::time::Duration as StdDuration;
-use tokio::sync::mpsc;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration as StdDuration,
+};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    time::Instant,
+};
 use uuid::Uuid;
 
-use crate::app::LogEntry;
-use crate::visitor::{PollingVisitor, StorageVisitor, Visitor};
+use crate::app::{LogEntry, LogLevel};
+use crate::config::{
+    DEFAULT_CERT_WARNING_DAYS, DEFAULT_HEARTBEAT_GRACE_SECONDS, DEFAULT_INTERVAL_SECONDS,
+    DEFAULT_MAX_CHECKS_PER_HOST, DEFAULT_MAX_CONCURRENT_CHECKS, DEFAULT_MAX_RETRIES,
+    DEFAULT_RETRY_BASE_DELAY_MS, DEFAULT_RETRY_MAX_DELAY_MS, DEFAULT_TIMEOUT_SECONDS,
+};
+use crate::visitor::{CheckSlots, PollingVisitor, StorageVisitor, Visitor};
+use scheduler::Scheduler;
+
+pub use assertion::Assertion;
+pub use dns::{parse_nameserver, RecordType};
+pub use heartbeat::{HeartbeatServer, HEARTBEAT_PATH};
+
+mod assertion;
+mod dns;
+mod heartbeat;
+mod scheduler;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Endpoint {
     pub id: Uuid,
     pub url: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub method: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub expected_status: Vec<u16>,
+    #[serde(default)]
+    pub assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub soft_assertions: Vec<Assertion>,
+    #[serde(default)]
+    pub send: Option<String>,
+    #[serde(default)]
+    pub expect: Option<String>,
+    #[serde(default)]
+    pub record_type: RecordType,
+    #[serde(default)]
+    pub nameserver: Option<String>,
+    #[serde(default)]
+    pub expected_records: Vec<String>,
+    #[serde(default)]
+    pub grpc_service: Option<String>,
+    #[serde(default)]
+    pub command: Vec<String>,
+    #[serde(default)]
+    pub latency_pattern: Option<String>,
+    #[serde(default)]
+    pub metric_pattern: Option<String>,
+    #[serde(default)]
+    pub token: Option<String>,
+    #[serde(default = "default_grace_seconds")]
+    pub grace_seconds: u64,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_interval_seconds")]
+    pub interval_seconds: u64,
+    #[serde(default)]
+    pub latency_warn_ms: Option<u64>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default = "default_cert_warning_days")]
+    pub cert_warning_days: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Paused endpoints keep their history but are not checked.
+    #[serde(default)]
+    pub paused: bool,
+}
+
+fn default_timeout_seconds() -> u64 {
+    DEFAULT_TIMEOUT_SECONDS
+}
+
+fn default_interval_seconds() -> u64 {
+    DEFAULT_INTERVAL_SECONDS
+}
+
+fn default_cert_warning_days() -> u64 {
+    DEFAULT_CERT_WARNING_DAYS
+}
+
+fn default_grace_seconds() -> u64 {
+    DEFAULT_HEARTBEAT_GRACE_SECONDS
+}
+
+impl Endpoint {
+    pub fn new(url: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            url,
+            name: None,
+            method: None,
+            headers: BTreeMap::new(),
+            body: None,
+            expected_status: Vec::new(),
+            assertions: Vec::new(),
+            soft_assertions: Vec::new(),
+            send: None,
+            expect: None,
+            record_type: RecordType::default(),
+            nameserver: None,
+            expected_records: Vec::new(),
+            grpc_service: None,
+            command: Vec::new(),
+            latency_pattern: None,
+            metric_pattern: None,
+            token: None,
+            grace_seconds: DEFAULT_HEARTBEAT_GRACE_SECONDS,
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            interval_seconds: DEFAULT_INTERVAL_SECONDS,
+            latency_warn_ms: None,
+            retry: RetryPolicy::default(),
+            cert_warning_days: DEFAULT_CERT_WARNING_DAYS,
+            tags: Vec::new(),
+            paused: false,
+        }
+    }
+
+    pub fn kind(&self) -> EndpointKind {
+        match self.url.split_once("://") {
+            Some(("tcp", _)) => EndpointKind::Tcp,
+            Some(("dns", _)) => EndpointKind::Dns,
+            Some(("ws" | "wss", _)) => EndpointKind::WebSocket,
+            Some(("grpc", _)) => EndpointKind::Grpc,
+            Some(("exec", _)) => EndpointKind::Exec,
+            Some(("heartbeat", _)) => EndpointKind::Heartbeat,
+            _ => EndpointKind::Http,
+        }
+    }
+
+    /// Path segment a heartbeat endpoint is pinged on. Without a configured
+    /// `token` the endpoint id is used, which stays the same across restarts
+    /// when history is kept in a database.
+    pub fn heartbeat_token(&self) -> String {
+        self.token
+            .clone()
+            .unwrap_or_else(|| self.id.simple().to_string())
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+
+    /// Any 2xx counts as up unless `expected_status` lists the accepted codes.
+    pub fn accepts_status(&self, code: u16) -> bool {
+        if self.expected_status.is_empty() {
+            (200..300).contains(&code)
+        } else {
+            self.expected_status.contains(&code)
+        }
+    }
+
+    pub fn host(&self) -> String {
+        let url = if self.url.contains("://") {
+            self.url.clone()
+        } else {
+            format!("http://{}", self.url)
+        };
+
+        reqwest::Url::parse(&url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| self.url.clone())
+    }
+}
+
+/// What kind of check an endpoint gets, derived from the scheme of its url.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointKind {
+    Http,
+    Tcp,
+    Dns,
+    WebSocket,
+    Grpc,
+    Exec,
+    Heartbeat,
+}
+
+impl EndpointKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EndpointKind::Http => "http",
+            EndpointKind::Tcp => "tcp",
+            EndpointKind::Dns => "dns",
+            EndpointKind::WebSocket => "websocket",
+            EndpointKind::Grpc => "grpc",
+            EndpointKind::Exec => "exec",
+            EndpointKind::Heartbeat => "heartbeat",
+        }
+    }
+}
+
+/// Outcome of a check. Degraded endpoints respond, but slower than their
+/// `latency_warn_ms` or with failing soft assertions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Up,
+    Degraded,
+    Down,
+}
+
+impl Status {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Up => "UP",
+            Status::Degraded => "DEGRADED",
+            Status::Down => "DOWN",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct PingResult {
     pub endpoint_id: Uuid,
-    pub status: bool,
+    pub status: Status,
     pub latency_ms: u64,
     pub timestamp: DateTime<Utc>,
+    pub status_code: Option<u16>,
+    pub error_kind: Option<ErrorKind>,
+    pub message: Option<String>,
+    pub attempts: u32,
+    pub certificate: Option<CertInfo>,
+    pub round_trip_ms: Option<u64>,
+    pub metric: Option<f64>,
+    pub timings: Option<Timings>,
+}
+
+impl PingResult {
+    pub fn up(endpoint_id: Uuid, latency_ms: u64) -> Self {
+        Self {
+            endpoint_id,
+            status: Status::Up,
+            latency_ms,
+            timestamp: Utc::now(),
+            status_code: None,
+            error_kind: None,
+            message: None,
+            attempts: 1,
+            certificate: None,
+            round_trip_ms: None,
+            metric: None,
+            timings: None,
+        }
+    }
+
+    pub fn down(endpoint_id: Uuid, latency_ms: u64, kind: ErrorKind, message: String) -> Self {
+        Self {
+            status: Status::Down,
+            error_kind: Some(kind),
+            message: Some(message),
+            ..Self::up(endpoint_id, latency_ms)
+        }
+    }
+
+    pub fn degraded(endpoint_id: Uuid, latency_ms: u64, message: String) -> Self {
+        Self {
+            status: Status::Degraded,
+            message: Some(message),
+            ..Self::up(endpoint_id, latency_ms)
+        }
+    }
+}
+
+/// Time spent in each phase of an HTTP check. When a redirect or the `HEAD`
+/// to `GET` fallback opens another connection, its phases are added on top.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub dns_ms: u64,
+    pub connect_ms: u64,
+    pub tls_ms: u64,
+    pub ttfb_ms: u64,
+    pub download_ms: u64,
+}
+
+impl Timings {
+    pub fn phases(&self) -> [(&'static str, u64); 5] {
+        [
+            ("dns", self.dns_ms),
+            ("connect", self.connect_ms),
+            ("tls", self.tls_ms),
+            ("ttfb", self.ttfb_ms),
+            ("download", self.download_ms),
+        ]
+    }
+}
+
+/// The certificate of an https endpoint's chain that expires first.
+#[derive(Debug, Clone)]
+pub struct CertInfo {
+    pub expires_at: DateTime<Utc>,
+    pub issuer: String,
+}
+
+impl CertInfo {
+    pub fn days_until_expiry(&self) -> i64 {
+        (self.expires_at - Utc::now()).num_days()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
+
+    /// True once the certificate expires within `warning_days`.
+    pub fn is_expiring(&self, warning_days: u64) -> bool {
+        self.expires_at - Utc::now() < Duration::days(warning_days as i64)
+    }
+}
+
+/// How often and how quickly a failed check is retried before the endpoint
+/// is reported down.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub retry_on: Vec<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay_ms: DEFAULT_RETRY_BASE_DELAY_MS,
+            max_delay_ms: DEFAULT_RETRY_MAX_DELAY_MS,
+            retry_on: vec![
+                ErrorKind::Dns,
+                ErrorKind::Connect,
+                ErrorKind::Timeout,
+                ErrorKind::Request,
+            ],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Backoff before retrying after the given (1-based) failed attempt:
+    /// `base_delay_ms` doubled per attempt, capped at `max_delay_ms`.
+    pub fn delay(&self, attempt: u32) -> StdDuration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(32);
+        StdDuration::from_millis(
+            self.base_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms),
+        )
+    }
+}
+
+/// Why a check failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Dns,
+    Connect,
+    Tls,
+    Timeout,
+    Status,
+    Assertion,
+    Request,
+    Exit,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Dns => "dns",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Tls => "tls",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Status => "status",
+            ErrorKind::Assertion => "assertion",
+            ErrorKind::Request => "request",
+            ErrorKind::Exit => "exit",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "dns" => Some(ErrorKind::Dns),
+            "connect" => Some(ErrorKind::Connect),
+            "tls" => Some(ErrorKind::Tls),
+            "timeout" => Some(ErrorKind::Timeout),
+            "status" => Some(ErrorKind::Status),
+            "assertion" => Some(ErrorKind::Assertion),
+            "request" => Some(ErrorKind::Request),
+            "exit" => Some(ErrorKind::Exit),
+            _ => None,
+        }
+    }
+}
+
+// how often the endpoint list is re-read so endpoints added at runtime get scheduled
+const RESCAN_INTERVAL: StdDuration = StdDuration::from_secs(1);
+
+/// Upper bounds on how many checks run at the same time, overall and
+/// against a single host.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimits {
+    pub max_checks: usize,
+    pub max_per_host: usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            max_checks: DEFAULT_MAX_CONCURRENT_CHECKS,
+            max_per_host: DEFAULT_MAX_CHECKS_PER_HOST,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct PingManager {
     storage: StorageVisitor,
-    interval: StdDuration,
+    limits: ConcurrencyLimits,
 }
 
 impl PingManager {
-    pub fn new(interval_seconds: u64) -> Self {
-        let storage = StorageVisitor::new();
-        Self {
-            storage,
-            interval: StdDuration::from_secs(interval_seconds),
-        }
+    pub fn new(storage: StorageVisitor, limits: ConcurrencyLimits) -> Self {
+        Self { storage, limits }
     }
 
-    pub fn add_endpoint(&mut self, url: String) -> Result<()> {
-        let endpoint = Endpoint {
-            id: Uuid::new_v4(),
-            url,
-        };
+    pub async fn add_endpoint(&mut self, mut endpoint: Endpoint) -> Result<()> {
+        // reuse the id of a stored endpoint with the same url so its history carries over
+        if let Some(existing) = self
+            .get_all_enpoints()
+            .await?
+            .into_iter()
+            .find(|existing| existing.url == endpoint.url)
+        {
+            endpoint.id = existing.id;
+        }
 
-        self.storage.add_endpoint(&endpoint)?;
+        self.storage.add_endpoint(&endpoint).await?;
         Ok(())
     }
 
-    pub fn get_all_enpoints(&self) -> Result<Vec<Endpoint>> {
-        let endpoints = self.storage.storage.endpoints.lock().unwrap();
-        Ok(endpoints.values().cloned().collect())
+    /// Sets aside stored endpoints whose url is not among `endpoints`: they are
+    /// paused so their history is kept, or removed together with it when
+    /// `prune` is set. Returns the endpoints that were set aside.
+    pub async fn retire_endpoints_except(
+        &mut self,
+        endpoints: &[Endpoint],
+        prune: bool,
+    ) -> Result<Vec<Endpoint>> {
+        let mut retired = Vec::new();
+        for stored in self.get_all_enpoints().await? {
+            if endpoints.iter().any(|endpoint| endpoint.url == stored.url) {
+                continue;
+            }
+            if prune {
+                self.storage.remove_endpoint(stored.id).await?;
+            } else if !stored.paused {
+                self.storage
+                    .add_endpoint(&Endpoint {
+                        paused: true,
+                        ..stored.clone()
+                    })
+                    .await?;
+            } else {
+                continue;
+            }
+            retired.push(stored);
+        }
+        Ok(retired)
+    }
+
+    pub async fn get_all_enpoints(&self) -> Result<Vec<Endpoint>> {
+        self.storage.get_endpoints().await
     }
 
     pub async fn start_polling(
@@ -57,24 +493,65 @@ impl PingManager {
         result_sender: mpsc::UnboundedSender<PingResult>,
         log_sender: mpsc::UnboundedSender<LogEntry>,
     ) {
-        let mut interval = tokio::time::interval(self.interval);
-        let mut polling_visitor = PollingVisitor::new(result_sender, log_sender);
+        let polling_visitor = PollingVisitor::new(result_sender, log_sender);
+        let mut scheduler = Scheduler::new();
+        let check_permits = Arc::new(Semaphore::new(self.limits.max_checks));
+        let mut host_permits: HashMap<String, Arc<Semaphore>> = HashMap::new();
+        let in_flight: Arc<Mutex<HashSet<Uuid>>> = Arc::new(Mutex::new(HashSet::new()));
 
         loop {
-            interval.tick().await;
-
-            match self.get_all_enpoints() {
+            match self.get_all_enpoints().await {
                 Ok(endpoints) => {
-                    for endpoint in endpoints {
-                        if let Err(e) = polling_visitor.visit_endpoint(&endpoint).await {
-                            eprintln!("Error polling {}: {}", endpoint.url, e)
+                    // heartbeats are pushed to the heartbeat server instead of polled
+                    let endpoints: Vec<Endpoint> = endpoints
+                        .into_iter()
+                        .filter(|endpoint| {
+                            endpoint.kind() != EndpointKind::Heartbeat && !endpoint.paused
+                        })
+                        .collect();
+                    let hosts: HashSet<String> = endpoints.iter().map(Endpoint::host).collect();
+                    host_permits.retain(|host, _| hosts.contains(host));
+
+                    for endpoint in scheduler.take_due(&endpoints, Instant::now()) {
+                        // a check slower than its interval is not stacked up a second time
+                        if !in_flight.lock().unwrap().insert(endpoint.id) {
+                            continue;
                         }
+
+                        let slots = CheckSlots {
+                            host: host_permits
+                                .entry(endpoint.host())
+                                .or_insert_with(|| {
+                                    Arc::new(Semaphore::new(self.limits.max_per_host))
+                                })
+                                .clone(),
+                            global: check_permits.clone(),
+                        };
+                        let in_flight = in_flight.clone();
+                        let mut polling_visitor = polling_visitor.clone().with_slots(slots);
+
+                        tokio::spawn(async move {
+                            if let Err(e) = polling_visitor.visit_endpoint(&endpoint).await {
+                                polling_visitor.send_log(
+                                    LogLevel::Error,
+                                    format!("Error polling {}: {}", endpoint.url, e),
+                                );
+                            }
+                            in_flight.lock().unwrap().remove(&endpoint.id);
+                        });
                     }
                 }
                 Err(e) => {
-                    eprintln!("Error getting endpoints: {}", e);
+                    polling_visitor
+                        .send_log(LogLevel::Error, format!("Error getting endpoints: {}", e));
                 }
             }
+
+            let rescan_at = Instant::now() + RESCAN_INTERVAL;
+            let wake_at = scheduler
+                .next_due()
+                .map_or(rescan_at, |next_due| next_due.min(rescan_at));
+            tokio::time::sleep_until(wake_at).await;
         }
     }
 
@@ -82,3 +559,77 @@ impl PingManager {
         self.storage.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn manager_with(urls: &[&str]) -> PingManager {
+        let mut manager = PingManager::new(StorageVisitor::new(), ConcurrencyLimits::default());
+        for url in urls {
+            manager
+                .add_endpoint(Endpoint::new(url.to_string()))
+                .await
+                .unwrap();
+        }
+        manager
+    }
+
+    fn urls(endpoints: &[Endpoint]) -> Vec<&str> {
+        let mut urls: Vec<&str> = endpoints.iter().map(|e| e.url.as_str()).collect();
+        urls.sort();
+        urls
+    }
+
+    #[tokio::test]
+    async fn endpoints_left_out_of_the_config_are_paused() {
+        let mut manager = manager_with(&["a.test", "b.test"]).await;
+        let config = [Endpoint::new("a.test".to_string())];
+
+        let retired = manager
+            .retire_endpoints_except(&config, false)
+            .await
+            .unwrap();
+        assert_eq!(urls(&retired), vec!["b.test"]);
+
+        let stored = manager.get_all_enpoints().await.unwrap();
+        assert_eq!(urls(&stored), vec!["a.test", "b.test"]);
+        assert!(stored.iter().all(|e| e.paused == (e.url == "b.test")));
+
+        // already paused endpoints are not reported again
+        let retired = manager
+            .retire_endpoints_except(&config, false)
+            .await
+            .unwrap();
+        assert!(retired.is_empty());
+    }
+
+    #[tokio::test]
+    async fn endpoints_left_out_of_the_config_are_removed_when_pruning() {
+        let mut manager = manager_with(&["a.test", "b.test"]).await;
+
+        let retired = manager
+            .retire_endpoints_except(&[Endpoint::new("a.test".to_string())], true)
+            .await
+            .unwrap();
+
+        assert_eq!(urls(&retired), vec!["b.test"]);
+        let stored = manager.get_all_enpoints().await.unwrap();
+        assert_eq!(urls(&stored), vec!["a.test"]);
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 500,
+            ..RetryPolicy::default()
+        };
+
+        let delays: Vec<u64> = (1..=5)
+            .map(|attempt| policy.delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, vec![100, 200, 400, 500, 500]);
+        assert_eq!(policy.delay(u32::MAX), StdDuration::from_millis(500));
+    }
+}
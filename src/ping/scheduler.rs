@@ -0,0 +1,224 @@
+use rand::Rng;
+use std::{collections::HashMap, time::Duration as StdDuration};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use super::Endpoint;
+
+// upper bound on the random offset either way from every scheduled check
+const MAX_JITTER: StdDuration = StdDuration::from_secs(30);
+// first checks after startup are only spread over a short window
+const MAX_STARTUP_JITTER: StdDuration = StdDuration::from_secs(2);
+
+struct Scheduled {
+    /// Time the check is planned for without jitter, one interval after the
+    /// previous one however late that ran.
+    slot: Instant,
+    next_due: Instant,
+    interval: StdDuration,
+}
+
+/// Keeps a next-due time per endpoint so each one is checked on its own
+/// `interval_seconds`.
+pub struct Scheduler {
+    entries: HashMap<Uuid, Scheduled>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the endpoints that are due at `now` and schedules their next
+    /// check. Endpoints seen for the first time are due after a short jitter so
+    /// a freshly loaded config does not fire every check in the same instant.
+    pub fn take_due(&mut self, endpoints: &[Endpoint], now: Instant) -> Vec<Endpoint> {
+        self.entries
+            .retain(|id, _| endpoints.iter().any(|endpoint| endpoint.id == *id));
+
+        let mut due = Vec::new();
+
+        for endpoint in endpoints {
+            let interval = StdDuration::from_secs(endpoint.interval_seconds.max(1));
+
+            let scheduled = self
+                .entries
+                .entry(endpoint.id)
+                .or_insert_with(|| Scheduled {
+                    slot: now,
+                    next_due: now + jitter(interval, MAX_STARTUP_JITTER),
+                    interval,
+                });
+
+            // an edited interval takes effect from now rather than after the old one elapses
+            if scheduled.interval != interval {
+                scheduled.interval = interval;
+                scheduled.slot = scheduled.slot.min(now + interval);
+                scheduled.next_due = scheduled.next_due.min(scheduled.slot);
+            }
+
+            if scheduled.next_due <= now {
+                scheduled.slot += interval;
+                // slots missed or close at hand are skipped, so a late check is
+                // not followed by a burst of them
+                while scheduled.slot < now + interval / 2 {
+                    scheduled.slot += interval;
+                }
+                scheduled.next_due = around(scheduled.slot, interval);
+                due.push(endpoint.clone());
+            }
+        }
+
+        due
+    }
+
+    pub fn next_due(&self) -> Option<Instant> {
+        self.entries
+            .values()
+            .map(|scheduled| scheduled.next_due)
+            .min()
+    }
+}
+
+// random offset of up to a tenth of the interval, capped at `cap`
+fn jitter(interval: StdDuration, cap: StdDuration) -> StdDuration {
+    let max = (interval / 10).min(cap);
+    if max.is_zero() {
+        return StdDuration::ZERO;
+    }
+    rand::thread_rng().gen_range(StdDuration::ZERO..max)
+}
+
+// `slot` moved by up to a tenth of the interval either way, capped at
+// MAX_JITTER, so the offsets don't add up from one check to the next
+fn around(slot: Instant, interval: StdDuration) -> Instant {
+    let max = (interval / 10).min(MAX_JITTER);
+    slot - max + jitter(interval, MAX_JITTER) * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::slice;
+
+    fn endpoint(interval_seconds: u64) -> Endpoint {
+        Endpoint {
+            interval_seconds,
+            ..Endpoint::new("example.com".to_string())
+        }
+    }
+
+    fn ids(endpoints: &[Endpoint]) -> Vec<Uuid> {
+        endpoints.iter().map(|endpoint| endpoint.id).collect()
+    }
+
+    #[test]
+    fn new_endpoints_are_due_within_the_startup_jitter() {
+        let endpoint = endpoint(60);
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+
+        scheduler.take_due(slice::from_ref(&endpoint), start);
+        let next_due = scheduler.next_due().unwrap();
+        assert!(next_due >= start && next_due < start + MAX_STARTUP_JITTER);
+
+        let due = scheduler.take_due(slice::from_ref(&endpoint), start + MAX_STARTUP_JITTER);
+        assert_eq!(ids(&due), vec![endpoint.id]);
+    }
+
+    #[test]
+    fn late_checks_do_not_push_back_the_next_ones() {
+        let endpoint = endpoint(60);
+        let interval = StdDuration::from_secs(60);
+        let max_jitter = interval / 10;
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+        scheduler.take_due(slice::from_ref(&endpoint), start);
+
+        for checks in 1..=100 {
+            // every check runs five seconds after it was due
+            let now = scheduler.next_due().unwrap() + StdDuration::from_secs(5);
+            let due = scheduler.take_due(slice::from_ref(&endpoint), now);
+            assert_eq!(due.len(), 1);
+
+            let slot = start + interval * checks;
+            let next_due = scheduler.next_due().unwrap();
+            assert!(
+                next_due >= slot - max_jitter && next_due < slot + max_jitter,
+                "check {} is off its slot by {:?}",
+                checks,
+                next_due.max(slot) - next_due.min(slot)
+            );
+        }
+    }
+
+    #[test]
+    fn missed_slots_are_skipped() {
+        let endpoint = endpoint(60);
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+        scheduler.take_due(slice::from_ref(&endpoint), start);
+        let first = scheduler.next_due().unwrap();
+        scheduler.take_due(slice::from_ref(&endpoint), first);
+
+        let late = start + StdDuration::from_secs(10 * 60 + 50);
+        assert_eq!(
+            scheduler.take_due(slice::from_ref(&endpoint), late).len(),
+            1
+        );
+
+        let next_due = scheduler.next_due().unwrap();
+        assert!(next_due >= start + StdDuration::from_secs(12 * 60 - 6));
+        assert!(next_due < start + StdDuration::from_secs(12 * 60 + 6));
+    }
+
+    #[test]
+    fn shortened_interval_takes_effect_right_away() {
+        let hourly = endpoint(3600);
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+        scheduler.take_due(slice::from_ref(&hourly), start);
+        let first = scheduler.next_due().unwrap();
+        scheduler.take_due(slice::from_ref(&hourly), first);
+
+        let every_minute = Endpoint {
+            interval_seconds: 60,
+            ..hourly.clone()
+        };
+        let edited_at = first + StdDuration::from_secs(1);
+        assert!(scheduler
+            .take_due(slice::from_ref(&every_minute), edited_at)
+            .is_empty());
+        assert_eq!(
+            scheduler.next_due(),
+            Some(edited_at + StdDuration::from_secs(60))
+        );
+
+        let due = scheduler.take_due(
+            slice::from_ref(&every_minute),
+            edited_at + StdDuration::from_secs(60),
+        );
+        assert_eq!(ids(&due), vec![hourly.id]);
+    }
+
+    #[test]
+    fn removed_endpoints_are_forgotten() {
+        let kept = endpoint(3600);
+        let removed = endpoint(60);
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+        scheduler.take_due(&[kept.clone(), removed.clone()], start);
+        scheduler.take_due(&[kept.clone(), removed], start + MAX_STARTUP_JITTER);
+
+        let due = scheduler.take_due(slice::from_ref(&kept), start + StdDuration::from_secs(120));
+
+        assert!(due.is_empty());
+        assert_eq!(
+            scheduler.entries.keys().copied().collect::<Vec<_>>(),
+            vec![kept.id]
+        );
+        assert!(scheduler.next_due().unwrap() > start + StdDuration::from_secs(3000));
+    }
+}
@@ -1,142 +1,699 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use chrono::{DateTime, Duration, Utc};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{Arc, Mutex},
 };
 use uuid::Uuid;
 
-use crate::ping::{Endpoint, PingResult};
+use crate::config::{
+    DEFAULT_HOURLY_ROLLUP_RETENTION_DAYS, DEFAULT_MINUTE_ROLLUP_RETENTION_DAYS,
+    DEFAULT_RAW_RETENTION_DAYS,
+};
+use crate::ping::{Endpoint, PingResult, Status};
+
+mod sqlite;
+
+pub use sqlite::SqliteStorage;
 
 #[derive(Debug, Clone)]
 pub struct EndpointStats {
     pub endpoint: Endpoint,
-    pub last_status: Option<bool>,
+    pub last_status: Option<Status>,
+    /// Share of checks that were up or degraded.
     pub uptime_percentage: f64,
+    /// Share of checks that were degraded, included in `uptime_percentage`.
+    pub degraded_percentage: f64,
     pub last_ping: Option<DateTime<Utc>>,
     pub avg_latency: Option<u64>,
+    pub latency: Option<LatencyStats>,
+    pub last_result: Option<PingResult>,
+}
+
+/// Latency percentiles of the checks that got an answer. Failed checks are
+/// left out, their latency is mostly time spent waiting for a timeout.
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyStats {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub max: u64,
+}
+
+impl LatencyStats {
+    fn from_latencies(mut latencies: Vec<u64>) -> Option<Self> {
+        latencies.sort_unstable();
+        let max = *latencies.last()?;
+        // nearest rank, so every percentile is a latency that was measured
+        let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+
+        Some(Self {
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max,
+        })
+    }
+}
+
+/// Uptime of one bucket, in the same terms as `EndpointStats`.
+#[derive(Debug, Clone)]
+pub struct UptimePoint {
+    /// Start of the bucket.
+    pub timestamp: DateTime<Utc>,
+    pub checks: usize,
+    pub uptime_percentage: f64,
+    pub degraded_percentage: f64,
+}
+
+impl UptimePoint {
+    /// Overall status of the bucket: down when every check failed, degraded
+    /// when only some did or some were degraded, `None` without checks.
+    pub fn status(&self) -> Option<Status> {
+        if self.checks == 0 {
+            None
+        } else if self.uptime_percentage == 0.0 {
+            Some(Status::Down)
+        } else if self.uptime_percentage < 100.0 || self.degraded_percentage > 0.0 {
+            Some(Status::Degraded)
+        } else {
+            Some(Status::Up)
+        }
+    }
+}
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+
+/// Resolutions results are rolled up into next to the raw results, finest
+/// first.
+const ROLLUP_RESOLUTIONS_MS: [i64; 2] = [MINUTE_MS, HOUR_MS];
+
+/// How long raw results and each rollup are kept.
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    pub raw: Duration,
+    pub minute_rollups: Duration,
+    pub hourly_rollups: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            raw: Duration::days(DEFAULT_RAW_RETENTION_DAYS as i64),
+            minute_rollups: Duration::days(DEFAULT_MINUTE_ROLLUP_RETENTION_DAYS as i64),
+            hourly_rollups: Duration::days(DEFAULT_HOURLY_ROLLUP_RETENTION_DAYS as i64),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    fn rollup_retention(&self, resolution_ms: i64) -> Duration {
+        if resolution_ms == MINUTE_MS {
+            self.minute_rollups
+        } else {
+            self.hourly_rollups
+        }
+    }
+}
+
+/// Rollup a history with `bucket_ms` long buckets is read from: the coarsest
+/// one that lines up with the buckets, as coarser rollups are kept longer and
+/// are cheaper to read. `None` means raw results.
+fn rollup_for_bucket(bucket_ms: i64) -> Option<i64> {
+    ROLLUP_RESOLUTIONS_MS
+        .into_iter()
+        .rev()
+        .find(|resolution_ms| bucket_ms % resolution_ms == 0)
+}
+
+/// Number of checks in a period, by outcome, and their summed latency.
+#[derive(Debug, Clone, Copy, Default)]
+struct StatusCounts {
+    total: usize,
+    up: usize,
+    degraded: usize,
+    latency_sum: u64,
+}
+
+impl StatusCounts {
+    fn add(&mut self, result: &PingResult) {
+        self.total += 1;
+        self.latency_sum += result.latency_ms;
+        match result.status {
+            Status::Up => self.up += 1,
+            Status::Degraded => self.degraded += 1,
+            Status::Down => {}
+        }
+    }
+
+    fn merge(&mut self, other: &StatusCounts) {
+        self.total += other.total;
+        self.up += other.up;
+        self.degraded += other.degraded;
+        self.latency_sum += other.latency_sum;
+    }
+
+    /// Mean latency of all checks, failed ones included.
+    fn avg_latency(&self) -> Option<u64> {
+        (self.total > 0).then(|| self.latency_sum / self.total as u64)
+    }
+
+    /// `(uptime_percentage, degraded_percentage)`, both 0 without checks.
+    fn percentages(&self) -> (f64, f64) {
+        if self.total == 0 {
+            return (0.0, 0.0);
+        }
+        let total = self.total as f64;
+        (
+            (self.up + self.degraded) as f64 / total * 100.0,
+            self.degraded as f64 / total * 100.0,
+        )
+    }
+}
+
+/// Backend for endpoints and their ping results. `StorageVisitor` talks to
+/// storage only through this trait.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()>;
+
+    /// Removes the endpoint together with its stored results.
+    async fn remove_endpoint(&self, endpoint_id: Uuid) -> Result<()>;
+
+    async fn get_endpoints(&self) -> Result<Vec<Endpoint>>;
+
+    /// Results of endpoints that are no longer stored are dropped.
+    async fn save_result(&self, result: &PingResult) -> Result<()>;
+
+    /// Every figure covers the checks since `since`. Uptime and the average
+    /// latency are read from the minute rollups, so they reach back as far as
+    /// those are kept, the latency percentiles only as far as raw results.
+    async fn get_endpoint_stats(&self, since: DateTime<Utc>) -> Result<Vec<EndpointStats>>;
+
+    /// Results of one endpoint since the given time, oldest first.
+    async fn get_results(&self, endpoint_id: Uuid, since: DateTime<Utc>)
+        -> Result<Vec<PingResult>>;
+
+    /// Uptime of one endpoint in `bucket`-long slices from `since` up to now,
+    /// slices without checks included. Read from rollups where the buckets
+    /// line up with one, so it reaches back further than the raw results.
+    async fn get_uptime_history(
+        &self,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+        bucket: Duration,
+    ) -> Result<Vec<UptimePoint>>;
+
+    /// Drops raw results and rollups older than the policy keeps them.
+    async fn apply_retention(&self, policy: &RetentionPolicy) -> Result<()>;
+}
+
+/// Results kept in memory per endpoint, the oldest are dropped beyond this.
+const MAX_RESULTS_PER_ENDPOINT: usize = 10_000;
+
+/// Results of one endpoint, oldest first. Every result is also counted into
+/// one rollup per resolution, which outlive the results.
+#[derive(Debug, Default)]
+struct EndpointHistory {
+    results: VecDeque<PingResult>,
+    rollups: [VecDeque<(i64, StatusCounts)>; ROLLUP_RESOLUTIONS_MS.len()],
+}
+
+impl EndpointHistory {
+    fn push(&mut self, result: PingResult) {
+        if self.results.len() >= MAX_RESULTS_PER_ENDPOINT {
+            self.results.pop_front();
+        }
+
+        let timestamp_ms = result.timestamp.timestamp_millis();
+        for (rollups, resolution_ms) in self.rollups.iter_mut().zip(ROLLUP_RESOLUTIONS_MS) {
+            let start = timestamp_ms - timestamp_ms.rem_euclid(resolution_ms);
+            // results come in about in order, so the slot is found near the end
+            let position = rollups.iter().rposition(|(other, _)| *other <= start);
+            match position {
+                Some(i) if rollups[i].0 == start => rollups[i].1.add(&result),
+                _ => {
+                    let mut counts = StatusCounts::default();
+                    counts.add(&result);
+                    rollups.insert(position.map_or(0, |i| i + 1), (start, counts));
+                }
+            }
+        }
+
+        self.results.push_back(result);
+    }
+
+    fn rollups(&self, resolution_ms: i64) -> &VecDeque<(i64, StatusCounts)> {
+        let i = ROLLUP_RESOLUTIONS_MS
+            .iter()
+            .position(|other| *other == resolution_ms)
+            .unwrap_or_default();
+        &self.rollups[i]
+    }
+
+    /// Rollups of one resolution that overlap the time since `since_ms`.
+    fn rollups_since(
+        &self,
+        resolution_ms: i64,
+        since_ms: i64,
+    ) -> impl Iterator<Item = &(i64, StatusCounts)> {
+        let rollups = self.rollups(resolution_ms);
+        let first = rollups.partition_point(|(start, _)| start + resolution_ms <= since_ms);
+        rollups.range(first..)
+    }
+
+    /// Results since the given time, oldest first. Results are stored as they
+    /// arrive, so the scan stops at the first older one.
+    fn since(&self, since: DateTime<Utc>) -> impl Iterator<Item = &PingResult> {
+        let count = self
+            .results
+            .iter()
+            .rev()
+            .take_while(|r| r.timestamp >= since)
+            .count();
+        self.results.range(self.results.len() - count..)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct MemoryStorage {
-    pub endpoints: Arc<Mutex<HashMap<Uuid, Endpoint>>>,
-    pub ping_results: Arc<Mutex<Vec<PingResult>>>,
+    endpoints: Arc<Mutex<HashMap<Uuid, Endpoint>>>,
+    histories: Arc<Mutex<HashMap<Uuid, EndpointHistory>>>,
 }
 
 impl MemoryStorage {
     pub fn new() -> Self {
         Self {
             endpoints: Arc::new(Mutex::new(HashMap::new())),
-            ping_results: Arc::new(Mutex::new(Vec::new())),
+            histories: Arc::new(Mutex::new(HashMap::new())),
         }
     }
+}
 
-    pub fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
         let mut endpoints = self.endpoints.lock().unwrap();
         endpoints.insert(endpoint.id, endpoint.clone());
         Ok(())
     }
 
-    pub fn save_result(&self, result: &PingResult) -> Result<()> {
-        let mut ping_results = self.ping_results.lock().unwrap();
-        ping_results.push(result.clone());
+    async fn remove_endpoint(&self, endpoint_id: Uuid) -> Result<()> {
+        self.endpoints.lock().unwrap().remove(&endpoint_id);
+        self.histories.lock().unwrap().remove(&endpoint_id);
+        Ok(())
+    }
+
+    async fn get_endpoints(&self) -> Result<Vec<Endpoint>> {
+        let endpoints = self.endpoints.lock().unwrap();
+        Ok(endpoints.values().cloned().collect())
+    }
 
-        if ping_results.len() > 50000 {
-            ping_results.drain(0..10000);
+    async fn save_result(&self, result: &PingResult) -> Result<()> {
+        let endpoints = self.endpoints.lock().unwrap();
+        // the endpoint was removed while it was being checked
+        if !endpoints.contains_key(&result.endpoint_id) {
+            return Ok(());
         }
 
+        self.histories
+            .lock()
+            .unwrap()
+            .entry(result.endpoint_id)
+            .or_default()
+            .push(result.clone());
         Ok(())
     }
 
-    pub fn get_endpoint_stats(&self) -> Result<Vec<EndpointStats>> {
+    async fn get_endpoint_stats(&self, since: DateTime<Utc>) -> Result<Vec<EndpointStats>> {
         let endpoints = self.endpoints.lock().unwrap();
-        let ping_results = self.ping_results.lock().unwrap();
+        let histories = self.histories.lock().unwrap();
+        let empty = EndpointHistory::default();
 
         let mut stats = Vec::new();
 
         for (_, endpoint) in endpoints.iter() {
-            let endpoint_results: Vec<&PingResult> = ping_results
-                .iter()
-                .filter(|r| r.endpoint_id == endpoint.id)
-                .collect();
-
-            let last_status = endpoint_results.last().map(|r| r.status);
-            let last_ping = endpoint_results.last().map(|r| r.timestamp);
-
-            let avg_latency = if !endpoint_results.is_empty() {
-                let total_latency: u64 = endpoint_results.iter().map(|r| r.latency_ms).sum();
-                Some(total_latency / endpoint_results.len() as u64)
-            } else {
-                None
-            };
-
-            let uptime_percentage = if !endpoint_results.is_empty() {
-                let successful_pings = endpoint_results.iter().filter(|r| r.status).count();
-                (successful_pings as f64 / endpoint_results.len() as f64) * 100.0
-            } else {
-                0.0
-            };
+            let history = histories.get(&endpoint.id).unwrap_or(&empty);
+
+            let last_result = history.results.back().cloned();
+            let mut counts = StatusCounts::default();
+            for (_, rollup) in history.rollups_since(MINUTE_MS, since.timestamp_millis()) {
+                counts.merge(rollup);
+            }
+            let (uptime_percentage, degraded_percentage) = counts.percentages();
+
+            let latency = LatencyStats::from_latencies(
+                history
+                    .since(since)
+                    .filter(|r| r.status != Status::Down)
+                    .map(|r| r.latency_ms)
+                    .collect(),
+            );
 
             stats.push(EndpointStats {
                 endpoint: endpoint.clone(),
-                last_status,
+                last_status: last_result.as_ref().map(|r| r.status),
                 uptime_percentage,
-                last_ping,
-                avg_latency,
+                degraded_percentage,
+                last_ping: last_result.as_ref().map(|r| r.timestamp),
+                avg_latency: counts.avg_latency(),
+                latency,
+                last_result,
             });
         }
 
         Ok(stats)
     }
 
-    pub fn get_uptime_history(
+    async fn get_results(
         &self,
         endpoint_id: Uuid,
-        hours: i64,
-    ) -> Result<Vec<(DateTime<Utc>, f64)>> {
-        let ping_results = self.ping_results.lock().unwrap();
-        let since = Utc::now() - Duration::hours(hours);
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PingResult>> {
+        let histories = self.histories.lock().unwrap();
+        Ok(histories
+            .get(&endpoint_id)
+            .map(|history| history.since(since).cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn get_uptime_history(
+        &self,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+        bucket: Duration,
+    ) -> Result<Vec<UptimePoint>> {
+        let histories = self.histories.lock().unwrap();
+        let bucket_ms = bucket.num_milliseconds().max(1);
+
+        let mut groups: HashMap<i64, StatusCounts> = HashMap::new();
+        if let Some(history) = histories.get(&endpoint_id) {
+            match rollup_for_bucket(bucket_ms) {
+                Some(resolution_ms) => {
+                    for (start, counts) in
+                        history.rollups_since(resolution_ms, since.timestamp_millis())
+                    {
+                        groups
+                            .entry(start.div_euclid(bucket_ms))
+                            .or_default()
+                            .merge(counts);
+                    }
+                }
+                None => {
+                    for result in history.since(since) {
+                        groups
+                            .entry(result.timestamp.timestamp_millis().div_euclid(bucket_ms))
+                            .or_default()
+                            .add(result);
+                    }
+                }
+            }
+        }
+
+        Ok(bucketed_history(since, bucket_ms, &groups))
+    }
+
+    async fn apply_retention(&self, policy: &RetentionPolicy) -> Result<()> {
+        let now = Utc::now();
+        let mut histories = self.histories.lock().unwrap();
+
+        for history in histories.values_mut() {
+            let raw_cutoff = now - policy.raw;
+            while history
+                .results
+                .front()
+                .is_some_and(|r| r.timestamp < raw_cutoff)
+            {
+                history.results.pop_front();
+            }
+
+            for (rollups, resolution_ms) in history.rollups.iter_mut().zip(ROLLUP_RESOLUTIONS_MS) {
+                let cutoff = (now - policy.rollup_retention(resolution_ms)).timestamp_millis();
+                while rollups.front().is_some_and(|(start, _)| *start < cutoff) {
+                    rollups.pop_front();
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns per-bucket counts keyed by `timestamp_millis / bucket_ms` into one
+/// uptime point per bucket from `since` up to now.
+fn bucketed_history(
+    since: DateTime<Utc>,
+    bucket_ms: i64,
+    groups: &HashMap<i64, StatusCounts>,
+) -> Vec<UptimePoint> {
+    let start = since.timestamp_millis().div_euclid(bucket_ms);
+    let end = Utc::now().timestamp_millis().div_euclid(bucket_ms);
 
-        let endpoint_results: Vec<&PingResult> = ping_results
+    (start..=end)
+        .map(|key| {
+            let counts = groups.get(&key).copied().unwrap_or_default();
+            let (uptime_percentage, degraded_percentage) = counts.percentages();
+
+            UptimePoint {
+                timestamp: DateTime::from_timestamp_millis(key * bucket_ms)
+                    .unwrap_or_else(Utc::now),
+                checks: counts.total,
+                uptime_percentage,
+                degraded_percentage,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+
+    use crate::ping::ErrorKind;
+
+    fn result_at(endpoint_id: Uuid, status: Status, timestamp: DateTime<Utc>) -> PingResult {
+        let result = match status {
+            Status::Up => PingResult::up(endpoint_id, 10),
+            Status::Degraded => PingResult::degraded(endpoint_id, 10, "slow".to_string()),
+            Status::Down => {
+                PingResult::down(endpoint_id, 10, ErrorKind::Timeout, "timeout".to_string())
+            }
+        };
+        PingResult {
+            timestamp,
+            ..result
+        }
+    }
+
+    /// `(start, total, up, degraded)` of each rollup, oldest first.
+    fn rollup_counts(
+        history: &EndpointHistory,
+        resolution_ms: i64,
+    ) -> Vec<(i64, usize, usize, usize)> {
+        history
+            .rollups(resolution_ms)
             .iter()
-            .filter(|r| r.endpoint_id == endpoint_id && r.timestamp >= since)
-            .collect();
+            .map(|(start, counts)| (*start, counts.total, counts.up, counts.degraded))
+            .collect()
+    }
 
-        if endpoint_results.is_empty() {
-            return Ok(vec![]);
+    #[test]
+    fn results_out_of_order_land_in_their_rollups() {
+        let id = Uuid::new_v4();
+        let hour = DateTime::from_timestamp_millis(1_700_000_000_000 / HOUR_MS * HOUR_MS).unwrap();
+        let at = |minutes: i64, seconds: i64| {
+            hour + Duration::minutes(minutes) + Duration::seconds(seconds)
+        };
+
+        let mut history = EndpointHistory::default();
+        for (status, timestamp) in [
+            (Status::Up, at(2, 10)),
+            (Status::Down, at(0, 30)),
+            (Status::Degraded, at(2, 50)),
+            (Status::Up, at(61, 0)),
+            (Status::Up, at(1, 59)),
+            (Status::Down, at(-1, 0)),
+        ] {
+            history.push(result_at(id, status, timestamp));
         }
 
-        let mut hourly_groups: HashMap<i64, Vec<&PingResult>> = HashMap::new();
+        let start = |minutes: i64| (hour + Duration::minutes(minutes)).timestamp_millis();
+        assert_eq!(
+            rollup_counts(&history, MINUTE_MS),
+            vec![
+                (start(-1), 1, 0, 0),
+                (start(0), 1, 0, 0),
+                (start(1), 1, 1, 0),
+                (start(2), 2, 1, 1),
+                (start(61), 1, 1, 0),
+            ]
+        );
+        assert_eq!(
+            rollup_counts(&history, HOUR_MS),
+            vec![
+                (start(-60), 1, 0, 0),
+                (start(0), 4, 2, 1),
+                (start(60), 1, 1, 0),
+            ]
+        );
+        assert_eq!(history.results.len(), 6);
+    }
+
+    #[test]
+    fn rollups_since_start_at_the_one_partly_in_range() {
+        let id = Uuid::new_v4();
+        let hour = DateTime::from_timestamp_millis(1_700_000_000_000 / HOUR_MS * HOUR_MS).unwrap();
 
-        for result in endpoint_results {
-            let hour_key = result.timestamp.timestamp() / 3600;
-            hourly_groups
-                .entry(hour_key)
-                .or_insert_with(Vec::new)
-                .push(result);
+        let mut history = EndpointHistory::default();
+        for minutes in [0, 1, 2, 3] {
+            history.push(result_at(id, Status::Up, hour + Duration::minutes(minutes)));
         }
 
-        let mut history = Vec::new();
-        let start_hour = (since.timestamp() / 3600) * 3600;
-        let end_hour = (Utc::now().timestamp() / 3600) * 3600;
+        let since = (hour + Duration::seconds(90)).timestamp_millis();
+        let starts: Vec<i64> = history
+            .rollups_since(MINUTE_MS, since)
+            .map(|(start, _)| (start - hour.timestamp_millis()) / MINUTE_MS)
+            .collect();
+        assert_eq!(starts, vec![1, 2, 3]);
+        assert_eq!(history.rollups_since(MINUTE_MS, i64::MAX).count(), 0);
+    }
 
-        for hour in (start_hour..=end_hour).step_by(3600) {
-            let hour_key = hour / 3600;
-            let uptime = if let Some(results) = hourly_groups.get(&hour_key) {
-                let successful = results.iter().filter(|r| r.status).count();
-                (successful as f64 / results.len() as f64) * 100.0
-            } else {
-                0.0
-            };
+    #[test]
+    fn history_buckets_are_read_from_the_coarsest_fitting_rollup() {
+        assert_eq!(rollup_for_bucket(MINUTE_MS), Some(MINUTE_MS));
+        assert_eq!(rollup_for_bucket(5 * MINUTE_MS), Some(MINUTE_MS));
+        assert_eq!(rollup_for_bucket(90 * MINUTE_MS), Some(MINUTE_MS));
+        assert_eq!(rollup_for_bucket(HOUR_MS), Some(HOUR_MS));
+        assert_eq!(rollup_for_bucket(12 * HOUR_MS), Some(HOUR_MS));
+        assert_eq!(rollup_for_bucket(30_000), None);
+        assert_eq!(rollup_for_bucket(90_000), None);
+    }
 
-            let timestamp = DateTime::from_timestamp(hour, 0)
-                .unwrap_or_else(|| Utc::now())
-                .with_timezone(&Utc);
+    /// Runs `test` against a memory storage and a temporary sqlite one.
+    async fn on_each_backend<F, Fut>(test: F)
+    where
+        F: Fn(Arc<dyn Storage>) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        println!("memory storage");
+        test(Arc::new(MemoryStorage::new())).await;
 
-            history.push((timestamp, uptime));
+        println!("sqlite storage");
+        let path = std::env::temp_dir().join(format!("pingme-{}.db", Uuid::new_v4()));
+        test(Arc::new(SqliteStorage::connect(&path).await.unwrap())).await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
         }
+    }
+
+    /// How many days ago each point with checks starts.
+    fn days_with_checks(points: &[UptimePoint], now: DateTime<Utc>) -> Vec<i64> {
+        points
+            .iter()
+            .filter(|point| point.checks > 0)
+            .map(|point| (now - point.timestamp).num_days())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn results_of_removed_endpoints_are_dropped() {
+        on_each_backend(|storage| async move {
+            let endpoint = Endpoint::new("example.com".to_string());
+            storage.add_endpoint(&endpoint).await.unwrap();
+            storage.remove_endpoint(endpoint.id).await.unwrap();
+
+            storage
+                .save_result(&PingResult::up(endpoint.id, 10))
+                .await
+                .unwrap();
+
+            let since = Utc::now() - Duration::hours(1);
+            let results = storage.get_results(endpoint.id, since).await.unwrap();
+            assert!(results.is_empty());
+            let history = storage
+                .get_uptime_history(endpoint.id, since, Duration::minutes(1))
+                .await
+                .unwrap();
+            assert!(history.iter().all(|point| point.checks == 0));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn stats_cover_the_checks_since_the_given_time() {
+        on_each_backend(|storage| async move {
+            let endpoint = Endpoint::new("example.com".to_string());
+            storage.add_endpoint(&endpoint).await.unwrap();
+
+            let now = Utc::now();
+            for (status, hours) in [(Status::Down, 3), (Status::Up, 1), (Status::Degraded, 0)] {
+                storage
+                    .save_result(&result_at(
+                        endpoint.id,
+                        status,
+                        now - Duration::hours(hours),
+                    ))
+                    .await
+                    .unwrap();
+            }
+
+            let stats = storage
+                .get_endpoint_stats(now - Duration::hours(2))
+                .await
+                .unwrap();
+            assert_eq!(stats[0].uptime_percentage, 100.0);
+            assert_eq!(stats[0].degraded_percentage, 50.0);
+            assert_eq!(stats[0].avg_latency, Some(10));
+            assert_eq!(stats[0].last_status, Some(Status::Degraded));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn retention_trims_each_tier_separately() {
+        on_each_backend(|storage| async move {
+            let endpoint = Endpoint::new("example.com".to_string());
+            storage.add_endpoint(&endpoint).await.unwrap();
+
+            let now = Utc::now();
+            for days in [400, 40, 10, 2] {
+                let timestamp = now - Duration::days(days);
+                storage
+                    .save_result(&result_at(endpoint.id, Status::Up, timestamp))
+                    .await
+                    .unwrap();
+            }
+
+            storage
+                .apply_retention(&RetentionPolicy {
+                    raw: Duration::days(7),
+                    minute_rollups: Duration::days(30),
+                    hourly_rollups: Duration::days(365),
+                })
+                .await
+                .unwrap();
+
+            let results = storage
+                .get_results(endpoint.id, now - Duration::days(401))
+                .await
+                .unwrap();
+            assert_eq!(results.len(), 1);
+
+            let minutes = storage
+                .get_uptime_history(endpoint.id, now - Duration::days(41), Duration::minutes(1))
+                .await
+                .unwrap();
+            assert_eq!(days_with_checks(&minutes, now), vec![10, 2]);
 
-        history.sort_by_key(|(timestamp, _)| *timestamp);
-        Ok(history)
+            let hours = storage
+                .get_uptime_history(endpoint.id, now - Duration::days(401), Duration::hours(1))
+                .await
+                .unwrap();
+            assert_eq!(days_with_checks(&hours, now), vec![40, 10, 2]);
+        })
+        .await;
     }
 }
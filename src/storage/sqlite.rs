@@ -0,0 +1,414 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow},
+    Row, SqlitePool,
+};
+use std::{collections::HashMap, path::Path};
+use uuid::Uuid;
+
+use super::{
+    bucketed_history, rollup_for_bucket, EndpointStats, LatencyStats, RetentionPolicy,
+    StatusCounts, Storage, UptimePoint, MINUTE_MS, ROLLUP_RESOLUTIONS_MS,
+};
+use crate::ping::{CertInfo, Endpoint, ErrorKind, PingResult, Status, Timings};
+
+#[derive(Debug, Clone)]
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+impl SqliteStorage {
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await
+            .with_context(|| format!("Failed to open database {}", path.display()))?;
+
+        sqlx::migrate!("./migrations")
+            .run(&pool)
+            .await
+            .context("Failed to run database migrations")?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl Storage for SqliteStorage {
+    async fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO endpoints (id, url, definition, created_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT (id) DO UPDATE SET url = excluded.url, definition = excluded.definition",
+        )
+        .bind(endpoint.id.to_string())
+        .bind(&endpoint.url)
+        .bind(serde_json::to_string(endpoint)?)
+        .bind(Utc::now().timestamp_millis())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn remove_endpoint(&self, endpoint_id: Uuid) -> Result<()> {
+        sqlx::query("DELETE FROM endpoints WHERE id = ?")
+            .bind(endpoint_id.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_endpoints(&self) -> Result<Vec<Endpoint>> {
+        let rows =
+            sqlx::query("SELECT id, url, definition FROM endpoints ORDER BY created_at, rowid")
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.iter()
+            .map(|row| {
+                let id = parse_id(row.get("id"))?;
+                // rows written before definitions were stored only carry the url
+                let endpoint = match row.get::<Option<String>, _>("definition") {
+                    Some(definition) => serde_json::from_str(&definition).with_context(|| {
+                        format!("Invalid endpoint definition in database for {}", id)
+                    })?,
+                    None => Endpoint::new(row.get("url")),
+                };
+                Ok(Endpoint { id, ..endpoint })
+            })
+            .collect()
+    }
+
+    async fn save_result(&self, result: &PingResult) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let inserted = sqlx::query(
+            "INSERT INTO ping_results
+                (endpoint_id, status, latency_ms, timestamp, status_code, error_kind, message, attempts,
+                 cert_expires_at, cert_issuer, round_trip_ms, metric, dns_ms, connect_ms, tls_ms,
+                 ttfb_ms, download_ms)
+             SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+             WHERE EXISTS (SELECT 1 FROM endpoints WHERE id = ?)",
+        )
+        .bind(result.endpoint_id.to_string())
+        .bind(status_to_db(result.status))
+        .bind(result.latency_ms as i64)
+        .bind(result.timestamp.timestamp_millis())
+        .bind(result.status_code)
+        .bind(result.error_kind.map(|kind| kind.as_str()))
+        .bind(&result.message)
+        .bind(result.attempts)
+        .bind(
+            result
+                .certificate
+                .as_ref()
+                .map(|cert| cert.expires_at.timestamp_millis()),
+        )
+        .bind(result.certificate.as_ref().map(|cert| &cert.issuer))
+        .bind(result.round_trip_ms.map(|ms| ms as i64))
+        .bind(result.metric)
+        .bind(result.timings.map(|t| t.dns_ms as i64))
+        .bind(result.timings.map(|t| t.connect_ms as i64))
+        .bind(result.timings.map(|t| t.tls_ms as i64))
+        .bind(result.timings.map(|t| t.ttfb_ms as i64))
+        .bind(result.timings.map(|t| t.download_ms as i64))
+        .bind(result.endpoint_id.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        // the endpoint was removed while it was being checked
+        if inserted.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        let timestamp_ms = result.timestamp.timestamp_millis();
+        for resolution_ms in ROLLUP_RESOLUTIONS_MS {
+            sqlx::query(
+                "INSERT INTO ping_rollups
+                    (endpoint_id, resolution_ms, bucket_start, total, up, degraded, latency_sum)
+                 VALUES (?, ?, ?, 1, ?, ?, ?)
+                 ON CONFLICT (endpoint_id, resolution_ms, bucket_start) DO UPDATE SET
+                    total = total + 1,
+                    up = up + excluded.up,
+                    degraded = degraded + excluded.degraded,
+                    latency_sum = latency_sum + excluded.latency_sum",
+            )
+            .bind(result.endpoint_id.to_string())
+            .bind(resolution_ms)
+            .bind(timestamp_ms - timestamp_ms.rem_euclid(resolution_ms))
+            .bind((result.status == Status::Up) as i64)
+            .bind((result.status == Status::Degraded) as i64)
+            .bind(result.latency_ms as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_endpoint_stats(&self, since: DateTime<Utc>) -> Result<Vec<EndpointStats>> {
+        let since_ms = since.timestamp_millis();
+
+        let totals: HashMap<String, StatusCounts> = sqlx::query(
+            "SELECT endpoint_id,
+                    SUM(total) AS total,
+                    SUM(up) AS up,
+                    SUM(degraded) AS degraded,
+                    SUM(latency_sum) AS latency_sum
+             FROM ping_rollups
+             WHERE endpoint_id IN (SELECT id FROM endpoints)
+               AND resolution_ms = ? AND bucket_start > ?
+             GROUP BY endpoint_id",
+        )
+        .bind(MINUTE_MS)
+        .bind(since_ms - MINUTE_MS)
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("endpoint_id"), status_counts(row)))
+        .collect();
+
+        let mut last_results: HashMap<Uuid, PingResult> = HashMap::new();
+        for row in sqlx::query(&format!(
+            "SELECT {} FROM ping_results WHERE id IN (
+                SELECT (SELECT id FROM ping_results WHERE endpoint_id = endpoints.id
+                        ORDER BY timestamp DESC, id DESC LIMIT 1)
+                FROM endpoints
+             )",
+            RESULT_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?
+        {
+            let result = row_to_result(&row)?;
+            last_results.insert(result.endpoint_id, result);
+        }
+
+        let mut latencies: HashMap<String, Vec<u64>> = HashMap::new();
+        for row in sqlx::query(
+            "SELECT endpoint_id, latency_ms FROM ping_results
+             WHERE endpoint_id IN (SELECT id FROM endpoints) AND timestamp >= ? AND status != 0",
+        )
+        .bind(since_ms)
+        .fetch_all(&self.pool)
+        .await?
+        {
+            let latency_ms: i64 = row.get("latency_ms");
+            latencies
+                .entry(row.get("endpoint_id"))
+                .or_default()
+                .push(latency_ms as u64);
+        }
+
+        let endpoints = self.get_endpoints().await?;
+        let mut stats = Vec::with_capacity(endpoints.len());
+
+        for endpoint in endpoints {
+            let id = endpoint.id.to_string();
+            let counts = totals.get(&id).copied().unwrap_or_default();
+            let last_result = last_results.remove(&endpoint.id);
+            let (uptime_percentage, degraded_percentage) = counts.percentages();
+
+            stats.push(EndpointStats {
+                endpoint,
+                last_status: last_result.as_ref().map(|r| r.status),
+                uptime_percentage,
+                degraded_percentage,
+                last_ping: last_result.as_ref().map(|r| r.timestamp),
+                avg_latency: counts.avg_latency(),
+                latency: LatencyStats::from_latencies(latencies.remove(&id).unwrap_or_default()),
+                last_result,
+            });
+        }
+
+        Ok(stats)
+    }
+
+    async fn get_results(
+        &self,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PingResult>> {
+        sqlx::query(&format!(
+            "SELECT {} FROM ping_results
+             WHERE endpoint_id = ? AND timestamp >= ? ORDER BY timestamp, id",
+            RESULT_COLUMNS
+        ))
+        .bind(endpoint_id.to_string())
+        .bind(since.timestamp_millis())
+        .fetch_all(&self.pool)
+        .await?
+        .iter()
+        .map(row_to_result)
+        .collect()
+    }
+
+    async fn get_uptime_history(
+        &self,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+        bucket: Duration,
+    ) -> Result<Vec<UptimePoint>> {
+        let bucket_ms = bucket.num_milliseconds().max(1);
+
+        let rows = match rollup_for_bucket(bucket_ms) {
+            Some(resolution_ms) => {
+                sqlx::query(
+                    "SELECT bucket_start / ? AS bucket_key,
+                            SUM(total) AS total,
+                            SUM(up) AS up,
+                            SUM(degraded) AS degraded,
+                            SUM(latency_sum) AS latency_sum
+                     FROM ping_rollups
+                     WHERE endpoint_id = ? AND resolution_ms = ? AND bucket_start > ?
+                     GROUP BY bucket_key",
+                )
+                .bind(bucket_ms)
+                .bind(endpoint_id.to_string())
+                .bind(resolution_ms)
+                .bind(since.timestamp_millis() - resolution_ms)
+                .fetch_all(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    "SELECT timestamp / ? AS bucket_key,
+                            COUNT(*) AS total,
+                            SUM(status = 1) AS up,
+                            SUM(status = 2) AS degraded,
+                            SUM(latency_ms) AS latency_sum
+                     FROM ping_results
+                     WHERE endpoint_id = ? AND timestamp >= ?
+                     GROUP BY bucket_key",
+                )
+                .bind(bucket_ms)
+                .bind(endpoint_id.to_string())
+                .bind(since.timestamp_millis())
+                .fetch_all(&self.pool)
+                .await?
+            }
+        };
+
+        let groups: HashMap<i64, StatusCounts> = rows
+            .iter()
+            .map(|row| (row.get("bucket_key"), status_counts(row)))
+            .collect();
+
+        Ok(bucketed_history(since, bucket_ms, &groups))
+    }
+
+    async fn apply_retention(&self, policy: &RetentionPolicy) -> Result<()> {
+        let now = Utc::now();
+
+        sqlx::query("DELETE FROM ping_results WHERE timestamp < ?")
+            .bind((now - policy.raw).timestamp_millis())
+            .execute(&self.pool)
+            .await?;
+
+        for resolution_ms in ROLLUP_RESOLUTIONS_MS {
+            sqlx::query("DELETE FROM ping_rollups WHERE resolution_ms = ? AND bucket_start < ?")
+                .bind(resolution_ms)
+                .bind((now - policy.rollup_retention(resolution_ms)).timestamp_millis())
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+const RESULT_COLUMNS: &str = "endpoint_id, status, latency_ms, timestamp, status_code, error_kind,
+    message, attempts, cert_expires_at, cert_issuer, round_trip_ms, metric, dns_ms, connect_ms,
+    tls_ms, ttfb_ms, download_ms";
+
+fn row_to_result(row: &SqliteRow) -> Result<PingResult> {
+    let timestamp: i64 = row.get("timestamp");
+    let latency_ms: i64 = row.get("latency_ms");
+
+    Ok(PingResult {
+        endpoint_id: parse_id(row.get("endpoint_id"))?,
+        status: status_from_db(row.get("status")),
+        latency_ms: latency_ms as u64,
+        timestamp: DateTime::from_timestamp_millis(timestamp).unwrap_or_default(),
+        status_code: row.get("status_code"),
+        error_kind: row
+            .get::<Option<String>, _>("error_kind")
+            .as_deref()
+            .and_then(ErrorKind::parse),
+        message: row.get("message"),
+        attempts: row.get("attempts"),
+        certificate: match (
+            row.get::<Option<i64>, _>("cert_expires_at"),
+            row.get::<Option<String>, _>("cert_issuer"),
+        ) {
+            (Some(expires_at), Some(issuer)) => Some(CertInfo {
+                expires_at: DateTime::from_timestamp_millis(expires_at).unwrap_or_default(),
+                issuer,
+            }),
+            _ => None,
+        },
+        round_trip_ms: row
+            .get::<Option<i64>, _>("round_trip_ms")
+            .map(|ms| ms as u64),
+        metric: row.get("metric"),
+        timings: match (
+            row.get::<Option<i64>, _>("dns_ms"),
+            row.get::<Option<i64>, _>("connect_ms"),
+            row.get::<Option<i64>, _>("tls_ms"),
+            row.get::<Option<i64>, _>("ttfb_ms"),
+            row.get::<Option<i64>, _>("download_ms"),
+        ) {
+            (Some(dns), Some(connect), Some(tls), Some(ttfb), Some(download)) => Some(Timings {
+                dns_ms: dns as u64,
+                connect_ms: connect as u64,
+                tls_ms: tls as u64,
+                ttfb_ms: ttfb as u64,
+                download_ms: download as u64,
+            }),
+            _ => None,
+        },
+    })
+}
+
+// 0 and 1 are the values stored back when status was a bool
+fn status_to_db(status: Status) -> i64 {
+    match status {
+        Status::Down => 0,
+        Status::Up => 1,
+        Status::Degraded => 2,
+    }
+}
+
+fn status_from_db(status: i64) -> Status {
+    match status {
+        1 => Status::Up,
+        2 => Status::Degraded,
+        _ => Status::Down,
+    }
+}
+
+fn status_counts(row: &SqliteRow) -> StatusCounts {
+    let total: i64 = row.get("total");
+    let up: i64 = row.get("up");
+    let degraded: i64 = row.get("degraded");
+    let latency_sum: i64 = row.get("latency_sum");
+    StatusCounts {
+        total: total as usize,
+        up: up as usize,
+        degraded: degraded as usize,
+        latency_sum: latency_sum as u64,
+    }
+}
+
+fn parse_id(id: String) -> Result<Uuid> {
+    Uuid::parse_str(&id).with_context(|| format!("Invalid endpoint id in database: {}", id))
+}
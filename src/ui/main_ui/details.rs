@@ -0,0 +1,320 @@
+use chrono::Utc;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style},
+    symbols,
+    text::{Line, Span},
+    widgets::{Axis, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{centered_rect, generate_time_labels, get_uptime_color};
+use crate::app::App;
+use crate::ping::{EndpointKind, PingResult, Timings, HEARTBEAT_PATH};
+
+pub fn render_endpoint_details(f: &mut Frame, app: &App) {
+    let Some(stats) = app.endpoints_stats.get(app.selected_endpoint) else {
+        return;
+    };
+    let endpoint = &stats.endpoint;
+
+    let area = centered_rect(70, 60, f.size());
+
+    let mut lines = vec![
+        detail_line("Name", endpoint.display_name().to_string()),
+        detail_line("URL", endpoint.url.clone()),
+        detail_line("Kind", endpoint.kind().as_str().to_string()),
+    ];
+
+    if endpoint.paused {
+        lines.push(detail_line(
+            "Paused",
+            "not checked until resumed with 'p'".to_string(),
+        ));
+    }
+
+    match endpoint.kind() {
+        EndpointKind::Http => lines.push(detail_line(
+            "Method",
+            endpoint
+                .method
+                .clone()
+                .unwrap_or_else(|| "HEAD, falling back to GET".to_string()),
+        )),
+        EndpointKind::Tcp | EndpointKind::WebSocket => {
+            if let Some(send) = &endpoint.send {
+                lines.push(detail_line("Send", format!("{:?}", send)));
+            }
+            if let Some(expect) = &endpoint.expect {
+                lines.push(detail_line("Expect", format!("{:?}", expect)));
+            }
+        }
+        EndpointKind::Grpc => lines.push(detail_line(
+            "Service",
+            endpoint
+                .grpc_service
+                .clone()
+                .unwrap_or_else(|| "server health".to_string()),
+        )),
+        EndpointKind::Heartbeat => {
+            lines.push(detail_line(
+                "Ping path",
+                format!("{}{}", HEARTBEAT_PATH, endpoint.heartbeat_token()),
+            ));
+            lines.push(detail_line(
+                "Expected every",
+                format!(
+                    "{}s (+{}s grace)",
+                    endpoint.interval_seconds, endpoint.grace_seconds
+                ),
+            ));
+        }
+        EndpointKind::Exec => {
+            lines.push(detail_line("Command", endpoint.command.join(" ")));
+            if let Some(pattern) = &endpoint.latency_pattern {
+                lines.push(detail_line("Latency pattern", pattern.clone()));
+            }
+            if let Some(pattern) = &endpoint.metric_pattern {
+                lines.push(detail_line("Metric pattern", pattern.clone()));
+            }
+        }
+        EndpointKind::Dns => {
+            lines.push(detail_line(
+                "Record type",
+                endpoint.record_type.as_str().to_string(),
+            ));
+            lines.push(detail_line(
+                "Nameserver",
+                endpoint
+                    .nameserver
+                    .clone()
+                    .unwrap_or_else(|| "system resolver".to_string()),
+            ));
+            if !endpoint.expected_records.is_empty() {
+                lines.push(detail_line(
+                    "Expected records",
+                    endpoint.expected_records.join(", "),
+                ));
+            }
+        }
+    }
+
+    lines.extend([
+        detail_line("Interval", format!("{}s", endpoint.interval_seconds)),
+        detail_line("Timeout", format!("{}s", endpoint.timeout_seconds)),
+        detail_line(
+            "Retries",
+            format!(
+                "{} ({}ms-{}ms backoff)",
+                endpoint.retry.max_retries,
+                endpoint.retry.base_delay_ms,
+                endpoint.retry.max_delay_ms
+            ),
+        ),
+    ]);
+
+    if !endpoint.tags.is_empty() {
+        lines.push(detail_line("Tags", endpoint.tags.join(", ")));
+    }
+
+    if let Some(warn_ms) = endpoint.latency_warn_ms {
+        lines.push(detail_line("Latency warning", format!("{}ms", warn_ms)));
+    }
+
+    lines.push(detail_line(
+        "Avg latency",
+        stats
+            .avg_latency
+            .map_or("-".to_string(), |ms| format!("{}ms", ms)),
+    ));
+
+    lines.push(detail_line(
+        "Uptime",
+        format!(
+            "{:.1}% ({:.1}% degraded)",
+            stats.uptime_percentage, stats.degraded_percentage
+        ),
+    ));
+
+    lines.push(Line::raw(""));
+
+    match &stats.last_result {
+        Some(result) => {
+            lines.push(Line::from(vec![
+                Span::styled("Last result: ", Style::default().fg(Color::Yellow)),
+                Span::styled(
+                    result.status.as_str(),
+                    Style::default().fg(get_uptime_color(result.status)),
+                ),
+            ]));
+            lines.push(detail_line(
+                "Checked at",
+                result.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            ));
+            lines.push(detail_line("Latency", format!("{}ms", result.latency_ms)));
+            if let Some(round_trip) = result.round_trip_ms {
+                lines.push(detail_line("Round trip", format!("{}ms", round_trip)));
+            }
+            if let Some(metric) = result.metric {
+                lines.push(detail_line("Metric", metric.to_string()));
+            }
+            if let Some(timings) = result.timings {
+                let phases: Vec<String> = timings
+                    .phases()
+                    .iter()
+                    .map(|(phase, ms)| format!("{} {}ms", phase, ms))
+                    .collect();
+                lines.push(detail_line("Timings", phases.join(", ")));
+            }
+            lines.push(detail_line("Attempts", result.attempts.to_string()));
+            lines.push(detail_line(
+                "HTTP status",
+                result
+                    .status_code
+                    .map_or("-".to_string(), |code| code.to_string()),
+            ));
+            lines.push(detail_line(
+                "Error",
+                result
+                    .error_kind
+                    .map_or("-".to_string(), |kind| kind.as_str().to_string()),
+            ));
+            lines.push(detail_line(
+                "Message",
+                result.message.clone().unwrap_or_else(|| "-".to_string()),
+            ));
+            if let Some(cert) = &result.certificate {
+                let color = if cert.is_expired() {
+                    Color::Red
+                } else if cert.is_expiring(endpoint.cert_warning_days) {
+                    Color::Yellow
+                } else {
+                    Color::Green
+                };
+                lines.push(Line::from(vec![
+                    Span::styled("Certificate: ", Style::default().fg(Color::Yellow)),
+                    Span::styled(
+                        format!(
+                            "expires {} ({} days)",
+                            cert.expires_at.format("%Y-%m-%d %H:%M:%S"),
+                            cert.days_until_expiry()
+                        ),
+                        Style::default().fg(color),
+                    ),
+                ]));
+                lines.push(detail_line("Issuer", cert.issuer.clone()));
+            }
+        }
+        None => lines.push(Line::styled(
+            "Not checked yet",
+            Style::default().fg(Color::Gray),
+        )),
+    }
+
+    let details = Paragraph::new(lines)
+        .block(
+            Block::default()
+                .title("Endpoint Details (Enter/Esc to close)")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan)),
+        )
+        .wrap(Wrap { trim: false });
+
+    f.render_widget(Clear, area);
+
+    let timed: Vec<&PingResult> = app
+        .selected_results
+        .iter()
+        .filter(|result| result.timings.is_some())
+        .collect();
+    if timed.is_empty() {
+        f.render_widget(details, area);
+        return;
+    }
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Percentage(45)])
+        .split(area);
+    f.render_widget(details, chunks[0]);
+    render_timing_chart(f, app, &timed, chunks[1]);
+}
+
+const PHASE_COLORS: [Color; 5] = [
+    Color::Magenta,
+    Color::Blue,
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+];
+
+// each phase is drawn on top of the ones before it, so the highest line is
+// the whole request and the gaps between lines are the phases
+fn render_timing_chart(f: &mut Frame, app: &App, results: &[&PingResult], area: Rect) {
+    let time_range = app.get_current_time_range();
+    let minutes = time_range.minutes() as f64;
+    let now = Utc::now();
+
+    let mut series: Vec<Vec<(f64, f64)>> = vec![Vec::new(); PHASE_COLORS.len()];
+    let mut max_total = 0;
+    for result in results {
+        let Some(timings) = result.timings else {
+            continue;
+        };
+        let minutes_ago = (now - result.timestamp).num_seconds() as f64 / 60.0;
+        let x = (minutes - minutes_ago).clamp(0.0, minutes);
+
+        let mut total = 0;
+        for (points, (_, ms)) in series.iter_mut().zip(timings.phases()) {
+            total += ms;
+            points.push((x, total as f64));
+        }
+        max_total = max_total.max(total);
+    }
+
+    let datasets = series
+        .iter()
+        .zip(Timings::default().phases())
+        .zip(PHASE_COLORS)
+        .map(|((points, (phase, _)), color)| {
+            Dataset::default()
+                .name(phase)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(color))
+                .graph_type(GraphType::Line)
+                .data(points)
+        })
+        .collect();
+
+    let top = (max_total as f64 * 1.1).max(1.0);
+    let chart = Chart::new(datasets)
+        .block(
+            Block::default()
+                .title(format!("{} Request Timings", time_range.display_name()))
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Cyan)),
+        )
+        // without the legend the lines can't be told apart
+        .hidden_legend_constraints((Constraint::Percentage(50), Constraint::Percentage(100)))
+        .x_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, minutes])
+                .labels(generate_time_labels(time_range)),
+        )
+        .y_axis(
+            Axis::default()
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, top])
+                .labels(vec!["0ms".into(), format!("{}ms", top as u64).into()]),
+        );
+
+    f.render_widget(chart, area);
+}
+
+fn detail_line(label: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{}: ", label), Style::default().fg(Color::Yellow)),
+        Span::raw(value),
+    ])
+}
@@ -0,0 +1,66 @@
+use ratatui::{
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::centered_rect;
+use crate::app::{App, EDIT_FIELDS};
+
+pub fn render_edit_form(f: &mut Frame, app: &App) {
+    let Some(form) = &app.edit_form else {
+        return;
+    };
+
+    let area = centered_rect(60, 70, f.size());
+    let block = Block::default()
+        .title(format!("Edit {}", form.endpoint.display_name()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    let inner = block.inner(area);
+
+    f.render_widget(Clear, area);
+    f.render_widget(block, area);
+
+    let mut constraints = vec![Constraint::Length(3); form.inputs.len()];
+    constraints.push(Constraint::Min(1));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(inner);
+
+    // the textarea widget is built against another ratatui, so the value is
+    // drawn as text with the terminal cursor placed on the focused field
+    for (i, (input, label)) in form.inputs.iter().zip(EDIT_FIELDS).enumerate() {
+        let focused = i == form.focused;
+        let field = Paragraph::new(input.lines().join("")).block(
+            Block::default()
+                .title(label)
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(if focused {
+                    Color::Yellow
+                } else {
+                    Color::Gray
+                })),
+        );
+        f.render_widget(field, chunks[i]);
+
+        if focused {
+            let (_, column) = input.cursor();
+            let x = (chunks[i].x + 1 + column as u16).min(chunks[i].right().saturating_sub(2));
+            f.set_cursor(x, chunks[i].y + 1);
+        }
+    }
+
+    if let Some(error) = &form.error {
+        f.render_widget(
+            Paragraph::new(Line::styled(
+                error.clone(),
+                Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+            )),
+            chunks[form.inputs.len()],
+        );
+    }
+}
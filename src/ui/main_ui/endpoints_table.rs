@@ -5,14 +5,21 @@ use ratatui::{
     Frame,
 };
 
+use super::get_uptime_color;
 use crate::app::App;
+use crate::ping::{ErrorKind, PingResult, Status};
+use crate::storage::EndpointStats;
 
 pub fn render_endpoints_table(f: &mut Frame, app: &App, area: Rect) {
     let header = Row::new([
-        Cell::from("URL").style(Style::default().fg(Color::Yellow)),
+        Cell::from("Endpoint").style(Style::default().fg(Color::Yellow)),
         Cell::from("Status").style(Style::default().fg(Color::Yellow)),
         Cell::from("Uptime %").style(Style::default().fg(Color::Yellow)),
-        Cell::from("Avg Latency").style(Style::default().fg(Color::Yellow)),
+        Cell::from("p50").style(Style::default().fg(Color::Yellow)),
+        Cell::from("p90").style(Style::default().fg(Color::Yellow)),
+        Cell::from("p99").style(Style::default().fg(Color::Yellow)),
+        Cell::from("Max").style(Style::default().fg(Color::Yellow)),
+        Cell::from("Cert Expiry").style(Style::default().fg(Color::Yellow)),
         Cell::from("Last Ping").style(Style::default().fg(Color::Yellow)),
     ])
     .height(1)
@@ -23,21 +30,26 @@ pub fn render_endpoints_table(f: &mut Frame, app: &App, area: Rect) {
         .iter()
         .enumerate()
         .map(|(i, stats)| {
-            let status = match stats.last_status {
-                Some(true) => "UP",
-                Some(false) => "DOWN",
-                None => "N/A",
+            let status = match &stats.last_result {
+                _ if stats.endpoint.paused => "PAUSED".to_string(),
+                Some(result) => status_label(result),
+                None => "N/A".to_string(),
             };
             let status_color = match stats.last_status {
-                Some(true) => Color::Green,
-                Some(false) => Color::Red,
+                _ if stats.endpoint.paused => Color::DarkGray,
+                Some(status) => get_uptime_color(status),
                 None => Color::Gray,
             };
 
             let uptime = format!("{:.1}%", stats.uptime_percentage);
-            let latency = stats
-                .avg_latency
-                .map_or("N/A".to_string(), |l| format!("{}ms", l));
+            let latency: Vec<String> = match stats.latency {
+                Some(latency) => [latency.p50, latency.p90, latency.p99, latency.max]
+                    .iter()
+                    .map(|ms| format!("{}ms", ms))
+                    .collect(),
+                None => vec!["N/A".to_string(); 4],
+            };
+            let (cert, cert_color) = cert_label(stats);
             let last_ping = stats
                 .last_ping
                 .map_or("Never".to_string(), |dt| dt.format("%H:%M:%S").to_string());
@@ -45,27 +57,37 @@ pub fn render_endpoints_table(f: &mut Frame, app: &App, area: Rect) {
             let mut style = Style::default();
             if i == app.selected_endpoint {
                 style = style.fg(Color::Yellow);
+            } else if stats.endpoint.paused {
+                style = style.fg(Color::DarkGray);
             }
 
-            Row::new(vec![
-                Cell::from(stats.endpoint.url.clone()).style(style),
+            let mut cells = vec![
+                Cell::from(stats.endpoint.display_name().to_string()).style(style),
                 Cell::from(status).style(Style::default().fg(status_color)),
                 Cell::from(uptime).style(style),
-                Cell::from(latency).style(style),
+            ];
+            cells.extend(latency.into_iter().map(|ms| Cell::from(ms).style(style)));
+            cells.extend([
+                Cell::from(cert).style(Style::default().fg(cert_color)),
                 Cell::from(last_ping).style(style),
-            ])
-            .bottom_margin(1)
+            ]);
+
+            Row::new(cells).bottom_margin(1)
         })
         .collect();
 
     let table = Table::new(
         rows,
         &[
-            Constraint::Percentage(40),
+            Constraint::Percentage(24),
+            Constraint::Percentage(14),
+            Constraint::Percentage(9),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
             Constraint::Percentage(10),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
-            Constraint::Percentage(20),
+            Constraint::Percentage(11),
         ],
     )
     .header(header)
@@ -78,3 +100,33 @@ pub fn render_endpoints_table(f: &mut Frame, app: &App, area: Rect) {
 
     f.render_stateful_widget(table, area, &mut app.table_state.clone());
 }
+
+fn status_label(result: &PingResult) -> String {
+    if result.status != Status::Down {
+        return result.status.as_str().to_string();
+    }
+
+    match (result.error_kind, result.status_code) {
+        (Some(ErrorKind::Status), Some(code)) => format!("DOWN {}", code),
+        (Some(kind), _) => format!("DOWN {}", kind.as_str()),
+        (None, _) => "DOWN".to_string(),
+    }
+}
+
+fn cert_label(stats: &EndpointStats) -> (String, Color) {
+    let Some(cert) = stats
+        .last_result
+        .as_ref()
+        .and_then(|result| result.certificate.as_ref())
+    else {
+        return ("-".to_string(), Color::Gray);
+    };
+
+    if cert.is_expired() {
+        ("EXPIRED".to_string(), Color::Red)
+    } else if cert.is_expiring(stats.endpoint.cert_warning_days) {
+        (format!("{}d", cert.days_until_expiry()), Color::Yellow)
+    } else {
+        (format!("{}d", cert.days_until_expiry()), Color::Green)
+    }
+}
@@ -1,15 +1,21 @@
+use chrono::Utc;
+use details::render_endpoint_details;
+use edit_form::render_edit_form;
 use endpoints_table::render_endpoints_table;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Style},
     symbols,
     text::{Line, Span},
-    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph},
+    widgets::{Axis, Block, Borders, Chart, Clear, Dataset, GraphType, Paragraph},
     Frame,
 };
 
-use crate::app::{App, InputMode, UptimeBlock};
+use crate::app::{App, InputMode, TimeRange, UptimeBlock};
+use crate::ping::Status;
 
+mod details;
+mod edit_form;
 mod endpoints_table;
 
 pub fn main_ui(f: &mut Frame, app: &App) {
@@ -26,88 +32,186 @@ pub fn main_ui(f: &mut Frame, app: &App) {
 
     render_endpoints_table(f, app, chunks[0]);
 
-    render_uptime_chart(f, app, chunks[1]);
+    if app.show_latency_chart {
+        render_latency_chart(f, app, chunks[1]);
+    } else {
+        render_uptime_chart(f, app, chunks[1]);
+    }
 
     render_uptime_blocks(f, app, chunks[2]);
 
     render_input_section(f, app, chunks[3]);
+
+    if app.show_details {
+        render_endpoint_details(f, app);
+    }
+
+    match app.input_mode {
+        InputMode::Editing => render_edit_form(f, app),
+        InputMode::ConfirmingRemoval => render_removal_confirmation(f, app),
+        InputMode::Normal | InputMode::Adding => {}
+    }
 }
 
 fn render_uptime_chart(f: &mut Frame, app: &App, area: Rect) {
     if !app.endpoints_stats.is_empty() && app.selected_endpoint < app.endpoints_stats.len() {
         let selected_endpoint = &app.endpoints_stats[app.selected_endpoint];
 
-        if let Some(data) = app
-            .uptime_history
-            .get(&selected_endpoint.endpoint.id)
-            .cloned()
-        {
-            if !data.is_empty() {
-                let time_range = app.get_current_time_range();
-                let datasets = vec![Dataset::default()
-                    .name(selected_endpoint.endpoint.url.clone())
-                    .marker(symbols::Marker::Braille)
-                    .style(Style::default().fg(Color::Cyan))
-                    .graph_type(GraphType::Line)
-                    .data(&data)];
-
-                let chart = Chart::new(datasets)
-                    .block(
-                        Block::default()
-                            .title(format!(
-                                "{} Uptime History - {}",
-                                time_range.display_name(),
-                                selected_endpoint.endpoint.url
-                            ))
-                            .borders(Borders::ALL)
-                            .border_style(Style::default().fg(Color::White)),
-                    )
-                    .x_axis(
-                        Axis::default()
-                            .title("Time")
-                            .style(Style::default().fg(Color::Gray))
-                            .bounds([0.0, time_range.get_duration_hours().unwrap_or(24) as f64])
-                            .labels(generate_time_labels(time_range)),
-                    )
-                    .y_axis(
-                        Axis::default()
-                            .title("Uptime %")
-                            .style(Style::default().fg(Color::Gray))
-                            .bounds([0.0, 100.0])
-                            .labels(vec![
-                                "0%".into(),
-                                "25%".into(),
-                                "50%".into(),
-                                "75%".into(),
-                                "100%".into(),
-                            ]),
-                    );
-
-                f.render_widget(chart, area);
-            } else {
-                let no_data = Paragraph::new("No uptime data available yet...")
-                    .block(
-                        Block::default()
-                            .title("Uptime History")
-                            .borders(Borders::ALL)
-                            .border_style(Style::default().fg(Color::Yellow)),
-                    )
-                    .style(Style::default().fg(Color::Gray));
-                f.render_widget(no_data, area);
-            }
+        let data = &app.uptime_history;
+        if !data.is_empty() {
+            let time_range = app.get_current_time_range();
+            let datasets = vec![Dataset::default()
+                .name(selected_endpoint.endpoint.display_name().to_string())
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Cyan))
+                .graph_type(GraphType::Line)
+                .data(data)];
+
+            let chart = Chart::new(datasets)
+                .block(
+                    Block::default()
+                        .title(format!(
+                            "{} Uptime History - {} ('l' for latency)",
+                            time_range.display_name(),
+                            selected_endpoint.endpoint.display_name()
+                        ))
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::White)),
+                )
+                .x_axis(
+                    Axis::default()
+                        .title("Time")
+                        .style(Style::default().fg(Color::Gray))
+                        .bounds([0.0, time_range.minutes() as f64])
+                        .labels(generate_time_labels(time_range)),
+                )
+                .y_axis(
+                    Axis::default()
+                        .title("Uptime %")
+                        .style(Style::default().fg(Color::Gray))
+                        .bounds([0.0, 100.0])
+                        .labels(vec![
+                            "0%".into(),
+                            "25%".into(),
+                            "50%".into(),
+                            "75%".into(),
+                            "100%".into(),
+                        ]),
+                );
+
+            f.render_widget(chart, area);
+        } else {
+            let no_data = Paragraph::new("No uptime data available yet...")
+                .block(
+                    Block::default()
+                        .title("Uptime History")
+                        .borders(Borders::ALL)
+                        .border_style(Style::default().fg(Color::Yellow)),
+                )
+                .style(Style::default().fg(Color::Gray));
+            f.render_widget(no_data, area);
         }
     }
 }
 
+fn render_latency_chart(f: &mut Frame, app: &App, area: Rect) {
+    let Some(selected_endpoint) = app.endpoints_stats.get(app.selected_endpoint) else {
+        return;
+    };
+    let time_range = app.get_current_time_range();
+    let minutes = time_range.minutes() as f64;
+    let now = Utc::now();
+
+    let mut answered = Vec::new();
+    let mut failed = Vec::new();
+    for result in &app.selected_results {
+        let minutes_ago = (now - result.timestamp).num_seconds() as f64 / 60.0;
+        let point = (
+            (minutes - minutes_ago).clamp(0.0, minutes),
+            result.latency_ms as f64,
+        );
+        match result.status {
+            Status::Down => failed.push(point),
+            _ => answered.push(point),
+        }
+    }
+
+    let block = Block::default()
+        .title(format!(
+            "{} Latency - {} ('l' for uptime)",
+            time_range.display_name(),
+            selected_endpoint.endpoint.display_name()
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::White));
+
+    if answered.is_empty() && failed.is_empty() {
+        let no_data = Paragraph::new("No latency data available yet...")
+            .block(block)
+            .style(Style::default().fg(Color::Gray));
+        f.render_widget(no_data, area);
+        return;
+    }
+
+    // failed checks mostly ran into the timeout, they are marked along the top
+    // instead of stretching the scale
+    let max = answered
+        .iter()
+        .map(|(_, latency)| *latency)
+        .fold(0.0, f64::max);
+    let top = (max * 1.1).max(1.0);
+    for point in &mut failed {
+        point.1 = point.1.min(top);
+    }
+
+    let datasets = vec![
+        Dataset::default()
+            .name("latency")
+            .marker(symbols::Marker::Braille)
+            .style(Style::default().fg(Color::Cyan))
+            .graph_type(GraphType::Line)
+            .data(&answered),
+        Dataset::default()
+            .name("down")
+            .marker(symbols::Marker::Dot)
+            .style(Style::default().fg(Color::Red))
+            .graph_type(GraphType::Scatter)
+            .data(&failed),
+    ];
+
+    let chart = Chart::new(datasets)
+        .block(block)
+        .x_axis(
+            Axis::default()
+                .title("Time")
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, minutes])
+                .labels(generate_time_labels(time_range)),
+        )
+        .y_axis(
+            Axis::default()
+                .title("Latency")
+                .style(Style::default().fg(Color::Gray))
+                .bounds([0.0, top])
+                .labels(vec![
+                    "0ms".into(),
+                    format!("{}ms", (top / 2.0) as u64).into(),
+                    format!("{}ms", top as u64).into(),
+                ]),
+        );
+
+    f.render_widget(chart, area);
+}
+
 fn render_uptime_blocks(f: &mut Frame, app: &App, area: Rect) {
     if !app.endpoints_stats.is_empty() && app.selected_endpoint < app.endpoints_stats.len() {
         let selected_endpoint = &app.endpoints_stats[app.selected_endpoint];
         let time_range = app.get_current_time_range();
 
-        if let Some(blocks) = app.uptime_blocks.get(&selected_endpoint.endpoint.id) {
+        if !app.uptime_blocks.is_empty() {
             let title = format!(
                 "Uptime Status - {} ({})",
-                selected_endpoint.endpoint.url,
+                selected_endpoint.endpoint.display_name(),
                 time_range.display_name()
             );
 
@@ -123,7 +227,7 @@ fn render_uptime_blocks(f: &mut Frame, app: &App, area: Rect) {
                 horizontal: 1,
             });
 
-            render_status_blocks(f, blocks, inner_area, time_range);
+            render_status_blocks(f, &app.uptime_blocks, inner_area);
         } else {
             let no_data = Paragraph::new("No uptime status data available...")
                 .block(
@@ -138,12 +242,7 @@ fn render_uptime_blocks(f: &mut Frame, app: &App, area: Rect) {
     }
 }
 
-fn render_status_blocks(
-    f: &mut Frame,
-    blocks: &[UptimeBlock],
-    area: Rect,
-    time_range: &crate::app::TimeRange,
-) {
+fn render_status_blocks(f: &mut Frame, blocks: &[UptimeBlock], area: Rect) {
     if blocks.is_empty() || area.height < 3 {
         return;
     }
@@ -152,11 +251,11 @@ fn render_status_blocks(
     let available_height = area.height.saturating_sub(2) as usize;
 
     let (block_width, block_height, blocks_per_row) =
-        calculate_block_dimensions(available_width, time_range);
+        calculate_block_dimensions(available_width, blocks.len());
 
     let mut lines = Vec::new();
 
-    for row in 0..(blocks.len() + blocks_per_row - 1) / blocks_per_row {
+    for row in 0..blocks.len().div_ceil(blocks_per_row) {
         let mut spans = Vec::new();
 
         for col in 0..blocks_per_row {
@@ -164,7 +263,7 @@ fn render_status_blocks(
 
             if block_index < blocks.len() {
                 let block = &blocks[block_index];
-                let color = get_uptime_color(block.status);
+                let color = block.status.map_or(Color::DarkGray, get_uptime_color);
                 let block_char = get_block_char(block_width);
 
                 spans.push(Span::styled(
@@ -187,8 +286,12 @@ fn render_status_blocks(
         lines.push(Line::from(vec![
             Span::styled("█", Style::default().fg(Color::Green)),
             Span::raw(" Up  "),
+            Span::styled("█", Style::default().fg(Color::Yellow)),
+            Span::raw(" Degraded  "),
             Span::styled("█", Style::default().fg(Color::Red)),
             Span::raw(" Down  "),
+            Span::styled("█", Style::default().fg(Color::DarkGray)),
+            Span::raw(" No data  "),
         ]));
     }
 
@@ -196,31 +299,21 @@ fn render_status_blocks(
     f.render_widget(status_paragraph, area);
 }
 
-fn calculate_block_dimensions(
-    available_width: usize,
-    time_range: &crate::app::TimeRange,
-) -> (usize, usize, usize) {
-    match time_range {
-        crate::app::TimeRange::Minutes(60) => {
-            let block_width = 1;
-            let block_height = 1;
-            let blocks_per_row = available_width / (block_width + 1);
-            (block_width, block_height, blocks_per_row.max(1))
-        }
-        _ => {
-            let block_width = 1;
-            let block_height = 1;
-            let blocks_per_row = available_width / (block_width + 1);
-            (block_width, block_height, blocks_per_row.max(1))
-        }
-    }
+/// Short ranges have few buckets, their blocks are widened to fill a row.
+fn calculate_block_dimensions(available_width: usize, block_count: usize) -> (usize, usize, usize) {
+    let block_width = (available_width / block_count.max(1))
+        .saturating_sub(1)
+        .clamp(1, 4);
+    let block_height = 1;
+    let blocks_per_row = available_width / (block_width + 1);
+    (block_width, block_height, blocks_per_row.max(1))
 }
 
-fn get_uptime_color(status: bool) -> Color {
-    if status {
-        Color::Green
-    } else {
-        Color::Red
+fn get_uptime_color(status: Status) -> Color {
+    match status {
+        Status::Up => Color::Green,
+        Status::Degraded => Color::Yellow,
+        Status::Down => Color::Red,
     }
 }
 
@@ -231,26 +324,69 @@ fn get_block_char(width: usize) -> &'static str {
     }
 }
 
-fn generate_time_labels(time_range: &crate::app::TimeRange) -> Vec<ratatui::text::Span<'static>> {
-    match time_range {
-        crate::app::TimeRange::Minutes(m) => {
-            vec![
-                format!("{}m ago", m).into(),
-                format!("{}m ago", m * 3 / 4).into(),
-                format!("{}m ago", m / 2).into(),
-                format!("{}m ago", m / 4).into(),
-                "Now".into(),
-            ]
-        }
+pub(super) fn generate_time_labels(time_range: &TimeRange) -> Vec<Span<'static>> {
+    let minutes = time_range.minutes() as f64;
+    vec![
+        format_ago(minutes).into(),
+        format_ago(minutes * 0.75).into(),
+        format_ago(minutes * 0.5).into(),
+        format_ago(minutes * 0.25).into(),
+        "Now".into(),
+    ]
+}
+
+fn format_ago(minutes: f64) -> String {
+    let (value, unit) = if minutes >= 2.0 * 24.0 * 60.0 {
+        (minutes / (24.0 * 60.0), "d")
+    } else if minutes >= 2.0 * 60.0 {
+        (minutes / 60.0, "h")
+    } else {
+        (minutes, "m")
+    };
+
+    if value.fract() == 0.0 {
+        format!("{}{} ago", value, unit)
+    } else {
+        format!("{:.1}{} ago", value, unit)
     }
 }
 
+fn render_removal_confirmation(f: &mut Frame, app: &App) {
+    let Some(endpoint) = app.selected_endpoint() else {
+        return;
+    };
+
+    let area = centered_rect(50, 20, f.size());
+    let text = vec![
+        Line::raw(format!("Remove {}?", endpoint.display_name())),
+        Line::raw("Its history is deleted with it."),
+        Line::raw(""),
+        Line::styled(
+            "y to remove, any other key to cancel",
+            Style::default().fg(Color::Gray),
+        ),
+    ];
+    let confirmation = Paragraph::new(text).block(
+        Block::default()
+            .title("Remove Endpoint")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Red)),
+    );
+
+    f.render_widget(Clear, area);
+    f.render_widget(confirmation, area);
+}
+
 fn render_input_section(f: &mut Frame, app: &App, area: Rect) {
     let input_block = Block::default()
         .borders(Borders::ALL)
         .title(match app.input_mode {
-            InputMode::Normal => "Press 'a' to add URL, 'q' to quit",
+            InputMode::Normal => {
+                "'a' add, 'e' edit, 'p' pause/resume, 'x' remove, Enter details, 'l' charts, 't'/'T' range, 'q' quit"
+            }
             InputMode::Adding => "Enter URL (ESC to cancel, Enter to confirm)",
+            InputMode::Editing => "Edit endpoint (Tab to move, Enter to save, ESC to cancel)",
+            InputMode::ConfirmingRemoval => "Remove endpoint? (y to confirm, any other key to cancel)",
         });
     f.render_widget(input_block, area);
 
@@ -263,3 +399,23 @@ fn render_input_section(f: &mut Frame, app: &App, area: Rect) {
         f.render_widget(Paragraph::new(text), inner_area);
     }
 }
+
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
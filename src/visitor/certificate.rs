@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Result};
+use chrono::DateTime;
+use reqwest::Url;
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, ServerName,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration as StdDuration, Instant, SystemTime},
+};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::ping::CertInfo;
+
+/// Certificates change rarely, so the one of a url is inspected at most this
+/// often and reused by the checks in between.
+const INSPECT_INTERVAL: StdDuration = StdDuration::from_secs(6 * 60 * 60);
+
+/// Last certificate inspected per url, with when to inspect it again, shared
+/// by all checks.
+#[derive(Clone, Default)]
+pub struct CertificateCache {
+    inspected: Arc<Mutex<HashMap<String, (Instant, CertInfo)>>>,
+}
+
+impl CertificateCache {
+    /// The certificate of `url`, inspected again once the last inspection is
+    /// older than `INSPECT_INTERVAL`. Failed inspections are not kept.
+    pub async fn get(&self, url: &str, timeout: StdDuration) -> Result<CertInfo> {
+        let cached = self
+            .inspected
+            .lock()
+            .unwrap()
+            .get(url)
+            .filter(|(stale_at, _)| Instant::now() < *stale_at)
+            .map(|(_, cert)| cert.clone());
+        if let Some(cert) = cached {
+            return Ok(cert);
+        }
+
+        let cert = inspect(url, timeout).await?;
+        self.inspected.lock().unwrap().insert(
+            url.to_string(),
+            (Instant::now() + INSPECT_INTERVAL, cert.clone()),
+        );
+        Ok(cert)
+    }
+}
+
+// the chain is only read, never trusted: expired and self-signed certificates
+// are exactly what has to be reported
+struct AcceptAnyCert;
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// Opens a TLS connection to the endpoint and returns the certificate of the
+/// presented chain that expires first.
+pub async fn inspect(url: &str, timeout: StdDuration) -> Result<CertInfo> {
+    let url = Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("url has no host"))?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port_or_known_default().unwrap_or(443);
+    let server_name = ServerName::try_from(host.as_str())
+        .map_err(|_| anyhow!("invalid server name {:?}", host))?;
+
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(AcceptAnyCert))
+        .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+
+    let stream = tokio::time::timeout(timeout, async {
+        let tcp = TcpStream::connect((host.as_str(), port)).await?;
+        connector.connect(server_name, tcp).await
+    })
+    .await
+    .map_err(|_| anyhow!("timed out after {}s", timeout.as_secs()))??;
+
+    let (_, connection) = stream.get_ref();
+    let chain = connection
+        .peer_certificates()
+        .ok_or_else(|| anyhow!("server sent no certificate"))?;
+
+    chain
+        .iter()
+        .map(|cert| parse_certificate(&cert.0))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .min_by_key(|info| info.expires_at)
+        .ok_or_else(|| anyhow!("server sent no certificate"))
+}
+
+fn parse_certificate(der: &[u8]) -> Result<CertInfo> {
+    let (_, cert) =
+        X509Certificate::from_der(der).map_err(|e| anyhow!("invalid certificate: {}", e))?;
+    let not_after = cert.validity().not_after;
+
+    Ok(CertInfo {
+        expires_at: DateTime::from_timestamp(not_after.timestamp(), 0)
+            .ok_or_else(|| anyhow!("certificate expiry {} is out of range", not_after))?,
+        issuer: cert.issuer().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use rcgen::{CertificateParams, DistinguishedName, DnType};
+    use rustls::{PrivateKey, ServerConfig};
+    use tokio::net::TcpListener;
+    use tokio_rustls::TlsAcceptor;
+
+    /// Serves a self-signed certificate for one connection, then stops
+    /// listening.
+    async fn serve_once(params: CertificateParams) -> String {
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![Certificate(cert.serialize_der().unwrap())],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let _ = acceptor.accept(tcp).await;
+        });
+
+        format!("https://127.0.0.1:{}", port)
+    }
+
+    #[tokio::test]
+    async fn inspect_reads_expiry_and_issuer_of_self_signed_certificate() {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]);
+        params.not_after = rcgen::date_time_ymd(2031, 5, 17);
+        params.distinguished_name = DistinguishedName::new();
+        params
+            .distinguished_name
+            .push(DnType::CommonName, "pingme test");
+        let url = serve_once(params).await;
+
+        let info = inspect(&url, StdDuration::from_secs(5)).await.unwrap();
+
+        assert_eq!(
+            info.expires_at,
+            Utc.with_ymd_and_hms(2031, 5, 17, 0, 0, 0).unwrap()
+        );
+        assert_eq!(info.issuer, "CN=pingme test");
+    }
+
+    #[tokio::test]
+    async fn cache_inspects_each_url_once_per_interval() {
+        let url = serve_once(CertificateParams::new(vec!["localhost".to_string()])).await;
+        let cache = CertificateCache::default();
+
+        let first = cache.get(&url, StdDuration::from_secs(5)).await.unwrap();
+        // the server is gone, so this one can only come from the cache
+        let second = cache.get(&url, StdDuration::from_secs(5)).await.unwrap();
+        assert_eq!(first.expires_at, second.expires_at);
+
+        cache.inspected.lock().unwrap().get_mut(&url).unwrap().0 = Instant::now();
+        assert!(cache.get(&url, StdDuration::from_secs(5)).await.is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use hickory_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::rr::RecordType as QueryType,
+    system_conf, TokioAsyncResolver,
+};
+use std::{
+    collections::BTreeSet,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::time::timeout;
+
+use super::elapsed_ms;
+use crate::ping::{parse_nameserver, Endpoint, ErrorKind, PingResult, RecordType};
+
+/// Resolves the name of a `dns://name` endpoint and reports the resolution
+/// time. With `expected_records` the answers have to match that set exactly.
+pub async fn check(endpoint: &Endpoint) -> PingResult {
+    let name = endpoint
+        .url
+        .trim_start_matches("dns://")
+        .trim_end_matches('/');
+
+    let resolver = match resolver(endpoint) {
+        Ok(resolver) => resolver,
+        Err(message) => return PingResult::down(endpoint.id, 0, ErrorKind::Request, message),
+    };
+
+    let limit = StdDuration::from_secs(endpoint.timeout_seconds);
+    let start = Instant::now();
+    // the resolver falls back from udp to tcp, which can take twice the timeout
+    let lookup = timeout(
+        limit,
+        resolver.lookup(name, query_type(endpoint.record_type)),
+    )
+    .await;
+    let latency = elapsed_ms(start);
+
+    let answers: BTreeSet<String> = match lookup {
+        Ok(Ok(lookup)) => lookup
+            .record_iter()
+            // a lookup can also return the cname chain that led to the answer
+            .filter(|record| record.record_type() == query_type(endpoint.record_type))
+            .filter_map(|record| record.data())
+            .map(|data| endpoint.record_type.normalize(&data.to_string()))
+            .collect(),
+        Ok(Err(e)) => {
+            let (kind, message) = describe_error(endpoint, name, &e);
+            return PingResult::down(endpoint.id, latency, kind, message);
+        }
+        Err(_) => {
+            return PingResult::down(
+                endpoint.id,
+                latency,
+                ErrorKind::Timeout,
+                format!("lookup timed out after {}s", limit.as_secs()),
+            )
+        }
+    };
+
+    if answers.is_empty() {
+        return PingResult::down(
+            endpoint.id,
+            latency,
+            ErrorKind::Dns,
+            format!("no {} records for {}", endpoint.record_type.as_str(), name),
+        );
+    }
+
+    if !endpoint.expected_records.is_empty() {
+        let expected: BTreeSet<String> = endpoint
+            .expected_records
+            .iter()
+            .map(|record| endpoint.record_type.normalize(record))
+            .collect();
+
+        if answers != expected {
+            return PingResult::down(
+                endpoint.id,
+                latency,
+                ErrorKind::Assertion,
+                format!(
+                    "{} records are [{}], expected [{}]",
+                    endpoint.record_type.as_str(),
+                    join(&answers),
+                    join(&expected)
+                ),
+            );
+        }
+    }
+
+    PingResult::up(endpoint.id, latency)
+}
+
+fn resolver(endpoint: &Endpoint) -> Result<TokioAsyncResolver, String> {
+    let (config, mut options) = match &endpoint.nameserver {
+        Some(nameserver) => {
+            let address = parse_nameserver(nameserver).map_err(|e| e.to_string())?;
+            let servers =
+                NameServerConfigGroup::from_ips_clear(&[address.ip()], address.port(), true);
+            (
+                ResolverConfig::from_parts(None, Vec::new(), servers),
+                ResolverOpts::default(),
+            )
+        }
+        None => system_conf::read_system_conf()
+            .map_err(|e| format!("failed to read system resolver config: {}", e))?,
+    };
+
+    // every check has to reach the nameserver, retries are left to the retry policy
+    options.cache_size = 0;
+    options.attempts = 1;
+    options.timeout = StdDuration::from_secs(endpoint.timeout_seconds);
+
+    Ok(TokioAsyncResolver::tokio(config, options))
+}
+
+fn query_type(record_type: RecordType) -> QueryType {
+    match record_type {
+        RecordType::A => QueryType::A,
+        RecordType::Aaaa => QueryType::AAAA,
+        RecordType::Cname => QueryType::CNAME,
+        RecordType::Txt => QueryType::TXT,
+    }
+}
+
+fn describe_error(endpoint: &Endpoint, name: &str, error: &ResolveError) -> (ErrorKind, String) {
+    match error.kind() {
+        ResolveErrorKind::NoRecordsFound { response_code, .. } => (
+            ErrorKind::Dns,
+            format!(
+                "no {} records for {} ({})",
+                endpoint.record_type.as_str(),
+                name,
+                response_code
+            ),
+        ),
+        ResolveErrorKind::Timeout => (ErrorKind::Timeout, error.to_string()),
+        _ => (ErrorKind::Dns, error.to_string()),
+    }
+}
+
+fn join(records: &BTreeSet<String>) -> String {
+    records.iter().cloned().collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hickory_resolver::proto::{
+        op::{Message, MessageType},
+        rr::{rdata::A, RData, Record},
+    };
+    use std::net::Ipv4Addr;
+    use tokio::net::UdpSocket;
+
+    use crate::ping::Status;
+
+    /// Answers every query with the given A records.
+    async fn stub_server(addresses: Vec<Ipv4Addr>) -> String {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut buffer = [0; 512];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buffer).await.unwrap();
+                let query = Message::from_vec(&buffer[..len]).unwrap();
+
+                let mut response = Message::new();
+                response
+                    .set_id(query.id())
+                    .set_message_type(MessageType::Response)
+                    .set_recursion_available(true)
+                    .add_queries(query.queries().to_vec());
+                for ip in &addresses {
+                    let name = query.queries()[0].name().clone();
+                    response.add_answer(Record::from_rdata(name, 60, RData::A(A(*ip))));
+                }
+
+                socket
+                    .send_to(&response.to_vec().unwrap(), peer)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        address.to_string()
+    }
+
+    fn endpoint(nameserver: String, expected_records: &[&str]) -> Endpoint {
+        Endpoint {
+            nameserver: Some(nameserver),
+            expected_records: expected_records.iter().map(|r| r.to_string()).collect(),
+            timeout_seconds: 5,
+            ..Endpoint::new("dns://app.example.test".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn check_matches_expected_records_in_any_order() {
+        let nameserver = stub_server(vec![
+            Ipv4Addr::new(192, 0, 2, 1),
+            Ipv4Addr::new(192, 0, 2, 2),
+        ])
+        .await;
+
+        let result = check(&endpoint(nameserver, &["192.0.2.2", "192.0.2.1"])).await;
+
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+    }
+
+    #[tokio::test]
+    async fn check_fails_when_records_differ_from_expected() {
+        let nameserver = stub_server(vec![Ipv4Addr::new(192, 0, 2, 1)]).await;
+
+        let result = check(&endpoint(nameserver, &["192.0.2.1", "192.0.2.2"])).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Assertion));
+        assert_eq!(
+            result.message.as_deref(),
+            Some("A records are [192.0.2.1], expected [192.0.2.1, 192.0.2.2]")
+        );
+    }
+
+    #[tokio::test]
+    async fn check_without_expected_records_accepts_any_answer() {
+        let nameserver = stub_server(vec![Ipv4Addr::new(192, 0, 2, 7)]).await;
+
+        let result = check(&endpoint(nameserver, &[])).await;
+
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+    }
+}
@@ -0,0 +1,249 @@
+use regex::Regex;
+use std::{
+    process::Stdio,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::{process::Command, time::timeout};
+
+use super::{elapsed_ms, PollingVisitor};
+use crate::app::LogLevel;
+use crate::ping::{Endpoint, ErrorKind, PingResult};
+
+// output beyond this is cut so a chatty script does not flood the developer log
+const MAX_LOGGED_LINES: usize = 20;
+
+/// Runs the `command` of an `exec://name` endpoint. Exit status 0 counts as
+/// up, stdout and stderr go to the developer log. `latency_pattern` and
+/// `metric_pattern` read a number from stdout, taken from the first capture
+/// group if the pattern has one.
+pub async fn check(visitor: &PollingVisitor, endpoint: &Endpoint) -> PingResult {
+    let Some((program, args)) = endpoint.command.split_first() else {
+        return PingResult::down(
+            endpoint.id,
+            0,
+            ErrorKind::Request,
+            "no command configured".to_string(),
+        );
+    };
+
+    let limit = StdDuration::from_secs(endpoint.timeout_seconds);
+    let start = Instant::now();
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // its own process group lets a timeout also stop whatever the command started
+    #[cfg(unix)]
+    command.process_group(0);
+
+    let child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                ErrorKind::Request,
+                format!("failed to run {}: {}", program, e),
+            )
+        }
+    };
+    let pid = child.id();
+
+    let output = match timeout(limit, child.wait_with_output()).await {
+        Ok(Ok(output)) => output,
+        Ok(Err(e)) => {
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                ErrorKind::Request,
+                format!("failed to wait for {}: {}", program, e),
+            )
+        }
+        Err(_) => {
+            kill_process_group(pid);
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                ErrorKind::Timeout,
+                format!("command timed out after {}s", limit.as_secs()),
+            );
+        }
+    };
+    let elapsed = elapsed_ms(start);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    log_output(visitor, &endpoint.url, "stdout", &stdout, LogLevel::Info);
+    log_output(visitor, &endpoint.url, "stderr", &stderr, LogLevel::Warning);
+
+    if !output.status.success() {
+        let message = match output.status.code() {
+            Some(code) => format!("exited with status {}", code),
+            None => "terminated by a signal".to_string(),
+        };
+        return PingResult::down(endpoint.id, elapsed, ErrorKind::Exit, message);
+    }
+
+    let latency = capture(&endpoint.latency_pattern, &stdout);
+    let metric = capture(&endpoint.metric_pattern, &stdout);
+
+    match (latency, metric) {
+        (Ok(latency), Ok(metric)) => PingResult {
+            metric,
+            ..PingResult::up(
+                endpoint.id,
+                latency.map_or(elapsed, |ms| ms.max(0.0) as u64),
+            )
+        },
+        (Err(message), _) | (_, Err(message)) => {
+            PingResult::down(endpoint.id, elapsed, ErrorKind::Assertion, message)
+        }
+    }
+}
+
+fn capture(pattern: &Option<String>, output: &str) -> Result<Option<f64>, String> {
+    let Some(pattern) = pattern else {
+        return Ok(None);
+    };
+
+    let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+    let captures = regex
+        .captures(output)
+        .ok_or_else(|| format!("output does not match /{}/", pattern))?;
+    let value = captures
+        .get(1)
+        .or_else(|| captures.get(0))
+        .map_or("", |m| m.as_str());
+
+    value
+        .trim()
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("{:?} matched by /{}/ is not a number", value, pattern))
+}
+
+fn log_output(visitor: &PollingVisitor, url: &str, stream: &str, output: &str, level: LogLevel) {
+    let lines: Vec<&str> = output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    for line in lines.iter().take(MAX_LOGGED_LINES) {
+        visitor.send_log(level.clone(), format!("{} {}: {}", url, stream, line));
+    }
+
+    if lines.len() > MAX_LOGGED_LINES {
+        visitor.send_log(
+            level,
+            format!(
+                "{} {}: ... {} more lines",
+                url,
+                stream,
+                lines.len() - MAX_LOGGED_LINES
+            ),
+        );
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(pid: Option<u32>) {
+    if let Some(pid) = pid {
+        // SAFETY: killpg has no memory safety requirements, the group was
+        // created for this command by process_group(0)
+        unsafe {
+            libc::killpg(pid as libc::pid_t, libc::SIGKILL);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(_pid: Option<u32>) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+    use tokio::sync::mpsc;
+
+    use crate::app::LogEntry;
+    use crate::ping::Status;
+
+    fn endpoint(script: &str) -> Endpoint {
+        Endpoint {
+            command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+            timeout_seconds: 1,
+            ..Endpoint::new("exec://test".to_string())
+        }
+    }
+
+    async fn run(endpoint: &Endpoint) -> (PingResult, Vec<String>) {
+        let (result_sender, _) = mpsc::unbounded_channel();
+        let (log_sender, mut log_receiver) = mpsc::unbounded_channel::<LogEntry>();
+        let visitor = PollingVisitor::new(result_sender, log_sender);
+
+        let result = check(&visitor, endpoint).await;
+
+        let mut logs = Vec::new();
+        while let Ok(entry) = log_receiver.try_recv() {
+            logs.push(entry.message);
+        }
+        (result, logs)
+    }
+
+    #[tokio::test]
+    async fn failing_exit_code_is_down_and_stderr_is_logged() {
+        let (result, logs) = run(&endpoint("echo 'disk full' >&2; exit 3")).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Exit));
+        assert_eq!(result.message.as_deref(), Some("exited with status 3"));
+        assert_eq!(logs, vec!["exec://test stderr: disk full"]);
+    }
+
+    #[tokio::test]
+    async fn timeout_kills_everything_the_command_started() {
+        let marker: PathBuf = std::env::temp_dir().join(format!("pingme-{}", uuid::Uuid::new_v4()));
+        let script = format!("(sleep 2; touch {}) & sleep 5", marker.display());
+
+        let (result, _) = run(&endpoint(&script)).await;
+        assert_eq!(result.error_kind, Some(ErrorKind::Timeout));
+        assert_eq!(
+            result.message.as_deref(),
+            Some("command timed out after 1s")
+        );
+
+        tokio::time::sleep(StdDuration::from_millis(2500)).await;
+        let survived = marker.exists();
+        let _ = fs::remove_file(&marker);
+        assert!(!survived, "a child of the command outlived the timeout");
+    }
+
+    #[tokio::test]
+    async fn latency_and_metric_are_read_from_stdout() {
+        let parsed = Endpoint {
+            latency_pattern: Some(r"took (\d+) ms".to_string()),
+            metric_pattern: Some(r"queue=(\d+)".to_string()),
+            ..endpoint("echo 'took 42 ms, queue=17'")
+        };
+
+        let (result, _) = run(&parsed).await;
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+        assert_eq!(result.latency_ms, 42);
+        assert_eq!(result.metric, Some(17.0));
+
+        let unmatched = Endpoint {
+            metric_pattern: Some(r"backlog=(\d+)".to_string()),
+            ..parsed
+        };
+        let (result, _) = run(&unmatched).await;
+        assert_eq!(result.error_kind, Some(ErrorKind::Assertion));
+        assert_eq!(
+            result.message.as_deref(),
+            Some(r"output does not match /backlog=(\d+)/")
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    error::Error as StdError,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::time::timeout;
+use tonic::{transport::Endpoint as GrpcEndpoint, Code, Status};
+use tonic_health::pb::{
+    health_check_response::ServingStatus, health_client::HealthClient, HealthCheckRequest,
+};
+
+use super::{elapsed_ms, error_chain};
+use crate::ping::{Endpoint, ErrorKind, PingResult};
+
+/// Calls `grpc.health.v1.Health/Check` on a `grpc://host:port` endpoint, for
+/// the configured `grpc_service` or the server as a whole.
+pub async fn check(endpoint: &Endpoint) -> PingResult {
+    let limit = StdDuration::from_secs(endpoint.timeout_seconds);
+    let start = Instant::now();
+
+    let outcome = timeout(limit, health_check(endpoint)).await;
+    let latency = elapsed_ms(start);
+
+    match outcome {
+        Ok(Ok(ServingStatus::Serving)) => PingResult::up(endpoint.id, latency),
+        // the server answers but cannot tell whether the service is healthy
+        Ok(Ok(ServingStatus::Unknown)) => {
+            PingResult::degraded(endpoint.id, latency, "health status UNKNOWN".to_string())
+        }
+        Ok(Ok(status)) => PingResult::down(
+            endpoint.id,
+            latency,
+            ErrorKind::Status,
+            format!("health status {}", status.as_str_name()),
+        ),
+        Ok(Err((kind, message))) => PingResult::down(endpoint.id, latency, kind, message),
+        Err(_) => PingResult::down(
+            endpoint.id,
+            latency,
+            ErrorKind::Timeout,
+            format!("health check timed out after {}s", limit.as_secs()),
+        ),
+    }
+}
+
+async fn health_check(endpoint: &Endpoint) -> Result<ServingStatus, (ErrorKind, String)> {
+    // tonic only knows http uris, grpc:// is plaintext http/2
+    let uri = endpoint.url.replacen("grpc://", "http://", 1);
+    let channel = GrpcEndpoint::from_shared(uri)
+        .map_err(|e| (ErrorKind::Request, format!("invalid url: {}", e)))?
+        .connect()
+        .await
+        .map_err(|e| {
+            let chain = error_chain(&e);
+            (classify_connect_error(&chain), chain)
+        })?;
+
+    let request = HealthCheckRequest {
+        service: endpoint.grpc_service.clone().unwrap_or_default(),
+    };
+
+    let response = HealthClient::new(channel)
+        .check(request)
+        .await
+        .map_err(|status| describe_status(endpoint, &status))?;
+
+    Ok(response.into_inner().status())
+}
+
+fn describe_status(endpoint: &Endpoint, status: &Status) -> (ErrorKind, String) {
+    match status.code() {
+        Code::NotFound => (
+            ErrorKind::Status,
+            format!(
+                "service {:?} is not known to the health server",
+                endpoint.grpc_service.as_deref().unwrap_or_default()
+            ),
+        ),
+        Code::Unimplemented => (
+            ErrorKind::Status,
+            "server does not implement grpc.health.v1.Health".to_string(),
+        ),
+        Code::DeadlineExceeded => (ErrorKind::Timeout, status.message().to_string()),
+        Code::Unavailable => (ErrorKind::Connect, status.message().to_string()),
+        code => {
+            let mut message = format!("{}: {}", code.description(), status.message());
+            if let Some(source) = status.source() {
+                message = format!("{}: {}", message, error_chain(source));
+            }
+            (ErrorKind::Request, message)
+        }
+    }
+}
+
+fn classify_connect_error(chain: &str) -> ErrorKind {
+    let chain = chain.to_lowercase();
+    if chain.contains("dns error") || chain.contains("failed to lookup address") {
+        ErrorKind::Dns
+    } else if chain.contains("timed out") {
+        ErrorKind::Timeout
+    } else {
+        ErrorKind::Connect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tonic::transport::{server::TcpIncoming, Server};
+
+    use crate::ping::Status as CheckStatus;
+
+    async fn health_server() -> String {
+        let (mut reporter, service) = tonic_health::server::health_reporter();
+        for (name, status) in [
+            ("orders", tonic_health::ServingStatus::Serving),
+            ("billing", tonic_health::ServingStatus::NotServing),
+            ("search", tonic_health::ServingStatus::Unknown),
+        ] {
+            reporter.set_service_status(name, status).await;
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(service)
+                .serve_with_incoming(incoming),
+        );
+
+        format!("grpc://{}", address)
+    }
+
+    async fn check_service(url: &str, service: &str) -> PingResult {
+        let endpoint = Endpoint {
+            grpc_service: Some(service.to_string()),
+            timeout_seconds: 5,
+            ..Endpoint::new(url.to_string())
+        };
+        check(&endpoint).await
+    }
+
+    #[tokio::test]
+    async fn check_maps_serving_statuses() {
+        let url = health_server().await;
+
+        let serving = check_service(&url, "orders").await;
+        assert_eq!(serving.status, CheckStatus::Up, "{:?}", serving.message);
+
+        let not_serving = check_service(&url, "billing").await;
+        assert_eq!(not_serving.status, CheckStatus::Down);
+        assert_eq!(not_serving.error_kind, Some(ErrorKind::Status));
+        assert_eq!(
+            not_serving.message.as_deref(),
+            Some("health status NOT_SERVING")
+        );
+
+        let unknown = check_service(&url, "search").await;
+        assert_eq!(unknown.status, CheckStatus::Degraded);
+        assert_eq!(unknown.message.as_deref(), Some("health status UNKNOWN"));
+    }
+
+    #[tokio::test]
+    async fn check_reports_services_unknown_to_the_server() {
+        let url = health_server().await;
+
+        let result = check_service(&url, "missing").await;
+
+        assert_eq!(result.status, CheckStatus::Down);
+        assert_eq!(
+            result.message.as_deref(),
+            Some("service \"missing\" is not known to the health server")
+        );
+    }
+}
@@ -1,23 +1,58 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
-use reqwest::Client;
-use std::time::{Duration as StdDuration, Instant};
-use tokio::sync::mpsc;
+use chrono::{DateTime, Duration, Utc};
+use std::{
+    error::Error as StdError,
+    sync::Arc,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
 use uuid::Uuid;
 
 use crate::app::{LogEntry, LogLevel};
-use crate::ping::{Endpoint, PingResult};
-use crate::storage::{EndpointStats, MemoryStorage};
+use crate::ping::{CertInfo, Endpoint, EndpointKind, ErrorKind, PingResult, Status};
+use crate::storage::{
+    EndpointStats, MemoryStorage, RetentionPolicy, SqliteStorage, Storage, UptimePoint,
+};
+
+mod certificate;
+mod dns;
+mod exec;
+mod grpc;
+mod http;
+mod tcp;
+mod websocket;
+
+use certificate::CertificateCache;
 
 #[allow(async_fn_in_trait)]
 pub trait Visitor {
     async fn visit_endpoint(&mut self, endpoint: &Endpoint) -> Result<()>;
 }
 
+/// The concurrency slots a check takes while it talks to its endpoint: one
+/// of its host's and one of the global ones.
+#[derive(Clone)]
+pub struct CheckSlots {
+    pub host: Arc<Semaphore>,
+    pub global: Arc<Semaphore>,
+}
+
+impl CheckSlots {
+    async fn acquire(&self) -> Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)> {
+        // wait for the host slot first so queued checks for a busy host
+        // don't hold global slots other hosts could use
+        let host = self.host.clone().acquire_owned().await.ok()?;
+        let global = self.global.clone().acquire_owned().await.ok()?;
+        Some((host, global))
+    }
+}
+
+#[derive(Clone)]
 pub struct PollingVisitor {
-    client: Client,
     result_sender: mpsc::UnboundedSender<PingResult>,
     log_sender: mpsc::UnboundedSender<LogEntry>,
+    certificates: CertificateCache,
+    slots: Option<CheckSlots>,
 }
 
 impl PollingVisitor {
@@ -25,20 +60,29 @@ impl PollingVisitor {
         result_sender: mpsc::UnboundedSender<PingResult>,
         log_sender: mpsc::UnboundedSender<LogEntry>,
     ) -> Self {
-        let client = Client::builder()
-            .timeout(StdDuration::from_secs(10))
-            .user_agent("pingme/1.0")
-            .build()
-            .unwrap_or_else(|_| Client::new());
-
         Self {
-            client,
             result_sender,
             log_sender,
+            certificates: CertificateCache::default(),
+            slots: None,
         }
     }
 
-    fn send_log(&self, level: LogLevel, message: String) {
+    pub fn with_slots(self, slots: CheckSlots) -> Self {
+        Self {
+            slots: Some(slots),
+            ..self
+        }
+    }
+
+    async fn acquire_slots(&self) -> Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)> {
+        match &self.slots {
+            Some(slots) => slots.acquire().await,
+            None => None,
+        }
+    }
+
+    pub fn send_log(&self, level: LogLevel, message: String) {
         let _ = self.log_sender.send(LogEntry {
             timestamp: Utc::now(),
             level,
@@ -47,9 +91,7 @@ impl PollingVisitor {
     }
 
     async fn ping_endpoint(&self, endpoint: &Endpoint) -> Result<PingResult> {
-        let start = Instant::now();
-
-        let url = if endpoint.url.starts_with("http://") || endpoint.url.starts_with("https://") {
+        let url = if endpoint.kind() != EndpointKind::Http || endpoint.url.contains("://") {
             endpoint.url.clone()
         } else {
             format!("http://{}", endpoint.url)
@@ -57,64 +99,214 @@ impl PollingVisitor {
 
         self.send_log(LogLevel::Info, format!("Pinging: {}", url));
 
-        //try with the head method first it's faster
-        let methods = [
-            |client: &Client, url: &str| client.head(url),
-            |client: &Client, url: &str| client.get(url),
-        ];
-
-        for (attempt, method) in methods.iter().enumerate() {
-            match method(&self.client, &url)
-                .timeout(StdDuration::from_secs(5))
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    let latency = start.elapsed().as_millis() as u64;
-                    let status = response.status().is_success();
-
-                    if status {
-                        self.send_log(LogLevel::Success, format!("{} - UP ({}ms)", url, latency));
-                    } else {
-                        self.send_log(
-                            LogLevel::Warning,
-                            format!("{} - Status: {} ({}ms)", url, response.status(), latency),
-                        );
-                    }
-
-                    return Ok(PingResult {
-                        endpoint_id: endpoint.id,
-                        latency_ms: latency,
-                        status,
-                        timestamp: Utc::now(),
-                    });
-                }
-                Err(e) => {
-                    if attempt == 0 && e.is_timeout() {
-                        continue;
-                    }
-                    if attempt == methods.len() - 1 {
-                        let latency = start.elapsed().as_millis() as u64;
-                        return Ok(PingResult {
-                            endpoint_id: endpoint.id,
-                            status: false,
-                            latency_ms: latency,
-                            timestamp: Utc::now(),
-                        });
-                    }
-                }
+        let policy = &endpoint.retry;
+        let mut attempt = 1;
+        let mut permits = self.acquire_slots().await;
+
+        let mut result = loop {
+            let mut result = self.check_once(endpoint, &url).await?;
+            result.attempts = attempt;
+
+            let retryable = result
+                .error_kind
+                .is_some_and(|kind| policy.retry_on.contains(&kind));
+
+            if result.status != Status::Down || !retryable || attempt > policy.max_retries {
+                break result;
             }
+
+            let delay = policy.delay(attempt);
+            self.send_log(
+                LogLevel::Warning,
+                format!(
+                    "{} - attempt {}/{} failed ({}): {}, retrying in {}ms",
+                    url,
+                    attempt,
+                    policy.max_retries + 1,
+                    result.error_kind.map_or("unknown", |kind| kind.as_str()),
+                    result.message.as_deref().unwrap_or("-"),
+                    delay.as_millis()
+                ),
+            );
+
+            // other checks can have the slots while this one waits
+            drop(permits);
+            tokio::time::sleep(delay).await;
+            permits = self.acquire_slots().await;
+            attempt += 1;
+        };
+
+        // nothing to inspect when the server could not be reached at all
+        let reachable = !matches!(
+            result.error_kind,
+            Some(ErrorKind::Dns | ErrorKind::Connect | ErrorKind::Timeout)
+        );
+        let tls = url.starts_with("https://") || url.starts_with("wss://");
+        if tls && reachable {
+            result.certificate = self.inspect_certificate(endpoint, &url).await;
         }
+        drop(permits);
 
-        let latency = start.elapsed().as_millis() as u64;
-        self.send_log(LogLevel::Error, format!("{} - DOWN: {}ms", url, latency));
-        Ok(PingResult {
-            endpoint_id: endpoint.id,
-            status: false,
-            latency_ms: latency,
-            timestamp: Utc::now(),
-        })
+        if result.status == Status::Up {
+            if let Some(message) = degradation(endpoint, &result) {
+                result.status = Status::Degraded;
+                result.message = Some(message);
+            }
+        }
+
+        self.log_result(&url, &result);
+
+        Ok(result)
+    }
+
+    async fn inspect_certificate(&self, endpoint: &Endpoint, url: &str) -> Option<CertInfo> {
+        let timeout = StdDuration::from_secs(endpoint.timeout_seconds);
+
+        let cert = match self.certificates.get(url, timeout).await {
+            Ok(cert) => cert,
+            Err(e) => {
+                self.send_log(
+                    LogLevel::Warning,
+                    format!("{} - failed to inspect certificate: {}", url, e),
+                );
+                return None;
+            }
+        };
+
+        let expires = cert.expires_at.format("%Y-%m-%d");
+        if cert.is_expired() {
+            self.send_log(
+                LogLevel::Error,
+                format!(
+                    "{} - certificate expired on {} (issuer: {})",
+                    url, expires, cert.issuer
+                ),
+            );
+        } else if cert.is_expiring(endpoint.cert_warning_days) {
+            self.send_log(
+                LogLevel::Warning,
+                format!(
+                    "{} - certificate expires in {} days on {} (issuer: {})",
+                    url,
+                    cert.days_until_expiry(),
+                    expires,
+                    cert.issuer
+                ),
+            );
+        }
+
+        Some(cert)
+    }
+
+    fn log_result(&self, url: &str, result: &PingResult) {
+        let attempts = if result.attempts > 1 {
+            format!(", {} attempts", result.attempts)
+        } else {
+            String::new()
+        };
+
+        if result.status == Status::Degraded {
+            self.send_log(
+                LogLevel::Warning,
+                format!(
+                    "{} - DEGRADED: {} ({}ms{})",
+                    url,
+                    result.message.as_deref().unwrap_or("-"),
+                    result.latency_ms,
+                    attempts
+                ),
+            );
+            return;
+        }
+
+        match (result.error_kind, &result.message) {
+            (None, _) => {
+                let round_trip = result
+                    .round_trip_ms
+                    .map_or(String::new(), |ms| format!(", round trip {}ms", ms));
+                let metric = result
+                    .metric
+                    .map_or(String::new(), |metric| format!(", metric {}", metric));
+                self.send_log(
+                    LogLevel::Success,
+                    format!(
+                        "{} - UP ({}ms{}{}{})",
+                        url, result.latency_ms, round_trip, metric, attempts
+                    ),
+                )
+            }
+            (Some(ErrorKind::Status | ErrorKind::Assertion), Some(message)) => self.send_log(
+                LogLevel::Warning,
+                format!(
+                    "{} - {} ({}ms{})",
+                    url, message, result.latency_ms, attempts
+                ),
+            ),
+            (Some(kind), message) => self.send_log(
+                LogLevel::Error,
+                format!(
+                    "{} - DOWN ({}): {} ({}ms{})",
+                    url,
+                    kind.as_str(),
+                    message.as_deref().unwrap_or("-"),
+                    result.latency_ms,
+                    attempts
+                ),
+            ),
+        }
     }
+
+    async fn check_once(&self, endpoint: &Endpoint, url: &str) -> Result<PingResult> {
+        match endpoint.kind() {
+            EndpointKind::Http => Ok(http::check(endpoint, url).await),
+            EndpointKind::Tcp => Ok(tcp::check(endpoint).await),
+            EndpointKind::Dns => Ok(dns::check(endpoint).await),
+            EndpointKind::WebSocket => Ok(websocket::check(endpoint).await),
+            EndpointKind::Grpc => Ok(grpc::check(endpoint).await),
+            EndpointKind::Exec => Ok(exec::check(self, endpoint).await),
+            EndpointKind::Heartbeat => Ok(PingResult::down(
+                endpoint.id,
+                0,
+                ErrorKind::Request,
+                "heartbeat endpoints are pushed to, not polled".to_string(),
+            )),
+        }
+    }
+}
+
+fn error_chain(error: &(dyn StdError + 'static)) -> String {
+    let mut messages = vec![error.to_string()];
+    let mut source = error.source();
+    while let Some(cause) = source {
+        let message = cause.to_string();
+        if !messages.iter().any(|m| m.contains(&message)) {
+            messages.push(message);
+        }
+        source = cause.source();
+    }
+    messages.join(": ")
+}
+
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+/// Why an endpoint that is up should be shown as degraded, if at all.
+fn degradation(endpoint: &Endpoint, result: &PingResult) -> Option<String> {
+    if let Some(warn_ms) = endpoint.latency_warn_ms {
+        if result.latency_ms > warn_ms {
+            return Some(format!(
+                "latency {}ms above {}ms",
+                result.latency_ms, warn_ms
+            ));
+        }
+    }
+
+    result
+        .certificate
+        .as_ref()
+        .filter(|cert| cert.is_expiring(endpoint.cert_warning_days))
+        .map(|cert| format!("certificate expires in {} days", cert.days_until_expiry()))
 }
 
 impl Visitor for PollingVisitor {
@@ -129,33 +321,143 @@ impl Visitor for PollingVisitor {
 
 #[derive(Clone)]
 pub struct StorageVisitor {
-    pub storage: MemoryStorage,
+    pub storage: Arc<dyn Storage>,
 }
 
 impl StorageVisitor {
     pub fn new() -> Self {
+        Self::with_storage(MemoryStorage::new())
+    }
+
+    pub fn with_storage(storage: impl Storage + 'static) -> Self {
         Self {
-            storage: MemoryStorage::new(),
+            storage: Arc::new(storage),
         }
     }
 
-    pub fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
-        self.storage.add_endpoint(endpoint)
+    pub async fn sqlite(path: &str) -> Result<Self> {
+        Ok(Self::with_storage(SqliteStorage::connect(path).await?))
+    }
+
+    pub async fn add_endpoint(&self, endpoint: &Endpoint) -> Result<()> {
+        self.storage.add_endpoint(endpoint).await
+    }
+
+    pub async fn remove_endpoint(&self, endpoint_id: Uuid) -> Result<()> {
+        self.storage.remove_endpoint(endpoint_id).await
+    }
+
+    pub async fn get_endpoints(&self) -> Result<Vec<Endpoint>> {
+        self.storage.get_endpoints().await
+    }
+
+    pub async fn save_result(&self, result: &PingResult) -> Result<()> {
+        self.storage.save_result(result).await
+    }
+
+    pub async fn get_endpoint_stats(&self, since: DateTime<Utc>) -> Result<Vec<EndpointStats>> {
+        self.storage.get_endpoint_stats(since).await
     }
 
-    pub fn save_result(&self, result: &PingResult) -> Result<()> {
-        self.storage.save_result(result)
+    pub async fn get_results(
+        &self,
+        endpoint_id: Uuid,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PingResult>> {
+        self.storage.get_results(endpoint_id, since).await
     }
 
-    pub fn get_endpoint_stats(&self) -> Result<Vec<EndpointStats>> {
-        self.storage.get_endpoint_stats()
+    pub async fn apply_retention(&self, policy: &RetentionPolicy) -> Result<()> {
+        self.storage.apply_retention(policy).await
     }
 
-    pub fn get_uptime_history(
+    pub async fn get_uptime_history(
         &self,
         endpoint_id: Uuid,
-        hours: i64,
-    ) -> Result<Vec<(DateTime<Utc>, f64)>> {
-        self.storage.get_uptime_history(endpoint_id, hours)
+        since: DateTime<Utc>,
+        bucket: Duration,
+    ) -> Result<Vec<UptimePoint>> {
+        self.storage
+            .get_uptime_history(endpoint_id, since, bucket)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    use crate::ping::RetryPolicy;
+
+    async fn refused_endpoint(base_delay_ms: u64) -> Endpoint {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        Endpoint {
+            retry: RetryPolicy {
+                max_retries: 2,
+                base_delay_ms,
+                max_delay_ms: base_delay_ms,
+                ..RetryPolicy::default()
+            },
+            ..Endpoint::new(url)
+        }
+    }
+
+    fn visitor() -> (PollingVisitor, mpsc::UnboundedReceiver<LogEntry>) {
+        let (result_sender, _) = mpsc::unbounded_channel();
+        let (log_sender, logs) = mpsc::unbounded_channel();
+        (PollingVisitor::new(result_sender, log_sender), logs)
+    }
+
+    #[tokio::test]
+    async fn failed_checks_are_tried_max_retries_plus_one_times() {
+        let (visitor, mut logs) = visitor();
+
+        let result = visitor
+            .ping_endpoint(&refused_endpoint(1).await)
+            .await
+            .unwrap();
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.attempts, 3);
+        let mut retries = Vec::new();
+        while let Ok(entry) = logs.try_recv() {
+            if matches!(entry.level, LogLevel::Warning) {
+                retries.push(entry.message);
+            }
+        }
+        assert_eq!(retries.len(), 2, "{:?}", retries);
+        assert!(retries[0].contains("attempt 1/3 failed (connect)"));
+        assert!(retries[1].contains("attempt 2/3 failed (connect)"));
+    }
+
+    #[tokio::test]
+    async fn slots_are_released_while_waiting_to_retry() {
+        let slots = CheckSlots {
+            host: Arc::new(Semaphore::new(1)),
+            global: Arc::new(Semaphore::new(1)),
+        };
+        let (visitor, _logs) = visitor();
+        let visitor = visitor.with_slots(slots.clone());
+        let endpoint = refused_endpoint(300).await;
+
+        let check = tokio::spawn(async move { visitor.ping_endpoint(&endpoint).await });
+
+        tokio::time::sleep(StdDuration::from_millis(150)).await;
+        assert_eq!(slots.host.available_permits(), 1);
+        assert_eq!(slots.global.available_permits(), 1);
+
+        // a slot taken by another check holds the retry back until it is free
+        let taken = slots.global.clone().acquire_owned().await.unwrap();
+        tokio::time::sleep(StdDuration::from_millis(300)).await;
+        assert!(!check.is_finished());
+        drop(taken);
+
+        let result = check.await.unwrap().unwrap();
+        assert_eq!(result.attempts, 3);
+        assert_eq!(slots.global.available_permits(), 1);
     }
 }
@@ -0,0 +1,211 @@
+use reqwest::Url;
+use std::{
+    io,
+    time::{Duration as StdDuration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::timeout,
+};
+
+use super::elapsed_ms;
+use crate::ping::{Endpoint, ErrorKind, PingResult};
+
+// how much of an unexpected reply ends up in the failure message
+const MAX_REPLY_PREVIEW: usize = 64;
+
+/// Connects to a `tcp://host:port` endpoint and reports the connect latency.
+/// With `send` the payload is written after connecting, with `expect` the
+/// server has to answer with a reply starting with the given prefix.
+pub async fn check(endpoint: &Endpoint) -> PingResult {
+    let limit = StdDuration::from_secs(endpoint.timeout_seconds);
+    let start = Instant::now();
+
+    let (host, port) = match address(&endpoint.url) {
+        Ok(address) => address,
+        Err(message) => return PingResult::down(endpoint.id, 0, ErrorKind::Request, message),
+    };
+
+    let mut stream = match timeout(limit, TcpStream::connect((host.as_str(), port))).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => {
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                classify_io_error(&e),
+                e.to_string(),
+            )
+        }
+        Err(_) => {
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                ErrorKind::Timeout,
+                format!("connect timed out after {}s", limit.as_secs()),
+            )
+        }
+    };
+    let latency = elapsed_ms(start);
+
+    let remaining = limit.saturating_sub(start.elapsed());
+    match timeout(remaining, exchange(&mut stream, endpoint)).await {
+        Ok(Ok(())) => PingResult::up(endpoint.id, latency),
+        Ok(Err((kind, message))) => PingResult::down(endpoint.id, latency, kind, message),
+        Err(_) => PingResult::down(
+            endpoint.id,
+            latency,
+            ErrorKind::Timeout,
+            format!("no reply within {}s", limit.as_secs()),
+        ),
+    }
+}
+
+async fn exchange(stream: &mut TcpStream, endpoint: &Endpoint) -> Result<(), (ErrorKind, String)> {
+    if let Some(payload) = &endpoint.send {
+        stream
+            .write_all(payload.as_bytes())
+            .await
+            .map_err(|e| (ErrorKind::Request, format!("failed to send payload: {}", e)))?;
+    }
+
+    let Some(expected) = &endpoint.expect else {
+        return Ok(());
+    };
+
+    let mut received = Vec::new();
+    let mut chunk = [0u8; 512];
+    while received.len() < expected.len() {
+        let read = stream
+            .read(&mut chunk)
+            .await
+            .map_err(|e| (ErrorKind::Request, format!("failed to read reply: {}", e)))?;
+        if read == 0 {
+            break;
+        }
+        received.extend_from_slice(&chunk[..read]);
+    }
+
+    check_reply(expected, &received)
+}
+
+/// Checks a reply against the `expect` prefix of a tcp or websocket endpoint.
+pub fn check_reply(expected: &str, received: &[u8]) -> Result<(), (ErrorKind, String)> {
+    if !received.starts_with(expected.as_bytes()) {
+        let preview = String::from_utf8_lossy(&received[..received.len().min(MAX_REPLY_PREVIEW)]);
+        return Err((
+            ErrorKind::Assertion,
+            format!(
+                "expected reply starting with {:?}, got {:?}",
+                expected, preview
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+fn address(url: &str) -> Result<(String, u16), String> {
+    let url = Url::parse(url).map_err(|e| format!("invalid url: {}", e))?;
+    let host = url
+        .host_str()
+        .ok_or("url has no host")?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = url.port().ok_or("url has no port")?;
+    Ok((host, port))
+}
+
+pub fn classify_io_error(error: &io::Error) -> ErrorKind {
+    if error.to_string().contains("failed to lookup address") {
+        ErrorKind::Dns
+    } else if error.kind() == io::ErrorKind::TimedOut {
+        ErrorKind::Timeout
+    } else {
+        ErrorKind::Connect
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{net::TcpListener, sync::oneshot};
+
+    use crate::ping::Status;
+
+    /// Accepts one connection, reads what the check sends and answers with
+    /// `reply`. The payload it read comes back through the receiver.
+    async fn banner_server(reply: &'static str) -> (String, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = oneshot::channel();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut payload = [0u8; 64];
+            let read = stream.read(&mut payload).await.unwrap();
+            let _ = sender.send(String::from_utf8_lossy(&payload[..read]).into_owned());
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        });
+
+        (format!("tcp://{}", address), receiver)
+    }
+
+    fn endpoint(url: String, expect: &str) -> Endpoint {
+        Endpoint {
+            send: Some("PING\r\n".to_string()),
+            expect: Some(expect.to_string()),
+            timeout_seconds: 1,
+            ..Endpoint::new(url)
+        }
+    }
+
+    #[tokio::test]
+    async fn check_sends_the_payload_and_matches_the_reply() {
+        let (url, payload) = banner_server("+PONG\r\n").await;
+
+        let result = check(&endpoint(url, "+PONG")).await;
+
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+        assert_eq!(payload.await.unwrap(), "PING\r\n");
+    }
+
+    #[tokio::test]
+    async fn check_fails_on_an_unexpected_reply() {
+        let (url, _) = banner_server("-ERR unknown command\r\n").await;
+
+        let result = check(&endpoint(url, "+PONG")).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Assertion));
+        assert_eq!(
+            result.message.as_deref(),
+            Some("expected reply starting with \"+PONG\", got \"-ERR unknown command\\r\\n\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn check_times_out_without_a_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+
+        let result = check(&endpoint(url, "+PONG")).await;
+        drop(listener);
+
+        assert_eq!(result.error_kind, Some(ErrorKind::Timeout));
+        assert_eq!(result.message.as_deref(), Some("no reply within 1s"));
+    }
+
+    #[tokio::test]
+    async fn check_reports_refused_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = check(&Endpoint::new(url)).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Connect));
+    }
+}
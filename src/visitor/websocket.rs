@@ -0,0 +1,252 @@
+use futures::{SinkExt, StreamExt};
+use std::time::{Duration as StdDuration, Instant};
+use tokio::{net::TcpStream, time::timeout};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        client::IntoClientRequest,
+        handshake::client::Request,
+        http::{HeaderName, HeaderValue},
+        Error as WsError, Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+use super::{
+    elapsed_ms,
+    tcp::{check_reply, classify_io_error},
+};
+use crate::ping::{Endpoint, ErrorKind, PingResult};
+
+// a server that never acknowledges the close frame does not hold up the check
+const CLOSE_TIMEOUT: StdDuration = StdDuration::from_secs(1);
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Performs the websocket upgrade for a `ws://` or `wss://` endpoint and
+/// reports the handshake latency. With `send` and `expect` the time until the
+/// expected reply arrives is reported as the round trip.
+pub async fn check(endpoint: &Endpoint) -> PingResult {
+    let limit = StdDuration::from_secs(endpoint.timeout_seconds);
+    let start = Instant::now();
+
+    let request = match request(endpoint) {
+        Ok(request) => request,
+        Err(message) => return PingResult::down(endpoint.id, 0, ErrorKind::Request, message),
+    };
+
+    let mut socket = match timeout(limit, connect_async(request)).await {
+        Ok(Ok((socket, _))) => socket,
+        Ok(Err(e)) => {
+            let (kind, message, status_code) = describe_error(e);
+            return PingResult {
+                status_code,
+                ..PingResult::down(endpoint.id, elapsed_ms(start), kind, message)
+            };
+        }
+        Err(_) => {
+            return PingResult::down(
+                endpoint.id,
+                elapsed_ms(start),
+                ErrorKind::Timeout,
+                format!("handshake timed out after {}s", limit.as_secs()),
+            )
+        }
+    };
+    let latency = elapsed_ms(start);
+
+    let remaining = limit.saturating_sub(start.elapsed());
+    let outcome = timeout(remaining, exchange(&mut socket, endpoint)).await;
+    let _ = timeout(CLOSE_TIMEOUT, socket.close(None)).await;
+
+    let result = match outcome {
+        Ok(Ok(round_trip_ms)) => PingResult {
+            round_trip_ms,
+            ..PingResult::up(endpoint.id, latency)
+        },
+        Ok(Err((kind, message))) => PingResult::down(endpoint.id, latency, kind, message),
+        Err(_) => PingResult::down(
+            endpoint.id,
+            latency,
+            ErrorKind::Timeout,
+            format!("no reply within {}s", limit.as_secs()),
+        ),
+    };
+
+    PingResult {
+        status_code: Some(101),
+        ..result
+    }
+}
+
+fn request(endpoint: &Endpoint) -> Result<Request, String> {
+    let mut request = endpoint
+        .url
+        .as_str()
+        .into_client_request()
+        .map_err(|e| format!("invalid url: {}", e))?;
+
+    for (name, value) in &endpoint.headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("invalid header name {:?}", name))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|_| format!("invalid value for header {:?}", name))?;
+        request.headers_mut().insert(name, value);
+    }
+
+    Ok(request)
+}
+
+async fn exchange(
+    socket: &mut Socket,
+    endpoint: &Endpoint,
+) -> Result<Option<u64>, (ErrorKind, String)> {
+    let start = Instant::now();
+
+    if let Some(payload) = &endpoint.send {
+        socket
+            .send(Message::Text(payload.clone()))
+            .await
+            .map_err(|e| (ErrorKind::Request, format!("failed to send message: {}", e)))?;
+    }
+
+    let Some(expected) = &endpoint.expect else {
+        return Ok(None);
+    };
+
+    loop {
+        let reply = match socket.next().await {
+            Some(Ok(Message::Text(text))) => text.into_bytes(),
+            Some(Ok(Message::Binary(data))) => data,
+            Some(Ok(Message::Close(_))) | None => {
+                return Err((
+                    ErrorKind::Assertion,
+                    "connection closed before a reply was received".to_string(),
+                ))
+            }
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => {
+                return Err((ErrorKind::Request, format!("failed to read reply: {}", e)))
+            }
+        };
+
+        check_reply(expected, &reply)?;
+        return Ok(Some(elapsed_ms(start)));
+    }
+}
+
+fn describe_error(error: WsError) -> (ErrorKind, String, Option<u16>) {
+    match error {
+        WsError::Io(e) => (classify_io_error(&e), e.to_string(), None),
+        WsError::Tls(e) => (ErrorKind::Tls, e.to_string(), None),
+        WsError::Http(response) => (
+            ErrorKind::Status,
+            format!("unexpected status {} during handshake", response.status()),
+            Some(response.status().as_u16()),
+        ),
+        e => (ErrorKind::Request, e.to_string(), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        sync::oneshot,
+    };
+    use tokio_tungstenite::accept_async;
+
+    use crate::ping::Status;
+
+    /// Accepts one websocket and answers every text message with "echo: "
+    /// and the message.
+    async fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = accept_async(stream).await.unwrap();
+            while let Some(Ok(message)) = socket.next().await {
+                if let Message::Text(text) = message {
+                    let _ = socket.send(Message::Text(format!("echo: {}", text))).await;
+                }
+            }
+        });
+
+        format!("ws://{}/socket", address)
+    }
+
+    fn endpoint(url: String, expect: &str) -> Endpoint {
+        Endpoint {
+            send: Some("hello".to_string()),
+            expect: Some(expect.to_string()),
+            timeout_seconds: 2,
+            ..Endpoint::new(url)
+        }
+    }
+
+    #[tokio::test]
+    async fn handshake_alone_is_enough_without_expect() {
+        let url = echo_server().await;
+
+        let result = check(&Endpoint::new(url)).await;
+
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+        assert_eq!(result.status_code, Some(101));
+        assert_eq!(result.round_trip_ms, None);
+    }
+
+    #[tokio::test]
+    async fn echo_reply_is_matched_and_timed() {
+        let url = echo_server().await;
+
+        let result = check(&endpoint(url, "echo: hello")).await;
+
+        assert_eq!(result.status, Status::Up, "{:?}", result.message);
+        assert!(result.round_trip_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn unexpected_echo_reply_fails_the_check() {
+        let url = echo_server().await;
+
+        let result = check(&endpoint(url, "hello")).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Assertion));
+        assert_eq!(
+            result.message.as_deref(),
+            Some("expected reply starting with \"hello\", got \"echo: hello\"")
+        );
+    }
+
+    #[tokio::test]
+    async fn refused_upgrade_reports_the_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/socket", listener.local_addr().unwrap());
+        let (sender, upgrade) = oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = [0u8; 1024];
+            let read = stream.read(&mut request).await.unwrap();
+            let _ = sender.send(String::from_utf8_lossy(&request[..read]).into_owned());
+            let _ = stream
+                .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+                .await;
+        });
+        let endpoint = Endpoint {
+            headers: [("x-token".to_string(), "secret".to_string())].into(),
+            ..endpoint(url, "echo: hello")
+        };
+
+        let result = check(&endpoint).await;
+
+        assert_eq!(result.status, Status::Down);
+        assert_eq!(result.error_kind, Some(ErrorKind::Status));
+        assert_eq!(result.status_code, Some(403));
+        assert!(upgrade.await.unwrap().contains("x-token: secret\r\n"));
+    }
+}